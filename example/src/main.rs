@@ -18,7 +18,9 @@ impl Default for RobotBase {
         task::spawn(|| {
             let mut robot = Robot::new().unwrap();
             robot.configure_button_bindings();
-            pros_command::robot::start_robot(robot).unwrap();
+            if let Err(error) = pros_command::robot::start_robot(robot) {
+                println!("Robot stopped: {}", error);
+            }
         });
         Self
     }
@@ -1,11 +1,10 @@
 use alloc::{rc::Rc, vec};
 use core::cell::RefCell;
 use pros::devices::Controller;
-use pros::devices::controller::ControllerButton;
 
 use pros_command::{command::{FunctionalCommand}, robot::ScheduledRobot, subsystem::Subsystem, CommandScheduler, run_once};
 use pros::prelude::*;
-use pros_command::controller::Trigger;
+use pros_command::controller::CommandController;
 
 use crate::subsystems::drivetrain::{DriveWithJoystickCommand, Drivetrain};
 
@@ -27,19 +26,15 @@ impl Robot {
         )
         .unwrap();
 
-        Trigger::button(Controller::Master, ControllerButton::A)
-            .on_true(run_once!({
-                    println!("Button A pressed");
-                }))
-            .on_false(run_once!({
-                    println!("Button A released");
-                }));
+        let controller = CommandController::new(Controller::Master);
+        let a = controller.a();
+        a.on_true(run_once!({
+            println!("Button A pressed");
+        }));
+        a.on_false(run_once!({
+            println!("Button A released");
+        }));
     }
 }
 
-impl ScheduledRobot for Robot {
-    fn periodic(&mut self) -> Result {
-        CommandScheduler::run()?;
-        Ok(())
-    }
-}
+impl ScheduledRobot for Robot {}
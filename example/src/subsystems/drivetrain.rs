@@ -7,6 +7,7 @@ use pros::devices::Controller;
 use pros::devices::controller::JoystickAxis;
 use pros::prelude::*;
 use pros_command::command::Command;
+use pros_command::shaping::{AxisShaper, WheelSpeeds};
 use pros_command::{AnyCommand, AnySubsystem};
 use pros_command::subsystem::{Subsystem, SubsystemRefExt};
 
@@ -59,6 +60,8 @@ pub struct DriveWithJoystickCommand {
     drivetrain: Rc<RefCell<Drivetrain>>,
     controller: Controller,
     requirements: Vec<AnySubsystem>,
+    left: AxisShaper,
+    right: AxisShaper,
 }
 
 impl DriveWithJoystickCommand {
@@ -67,6 +70,8 @@ impl DriveWithJoystickCommand {
             requirements: vec![AnySubsystem(drivetrain.clone())],
             drivetrain,
             controller,
+            left: AxisShaper::new().deadband(0.05).expo(2.0).slew_rate(4.0),
+            right: AxisShaper::new().deadband(0.05).expo(2.0).slew_rate(4.0),
         }
     }
 }
@@ -78,9 +83,10 @@ impl Command for DriveWithJoystickCommand {
 
     fn execute(&mut self) -> Result {
         let left_y = self.controller.joystick_axis(JoystickAxis::LeftY)?;
-        self.drivetrain.borrow().left_motor.set_output(left_y)?;
         let right_y = self.controller.joystick_axis(JoystickAxis::RightY)?;
-        self.drivetrain.borrow().right_motor.set_output(right_y)?;
+        let speeds = WheelSpeeds::tank(self.left.shape(left_y), self.right.shape(right_y));
+        self.drivetrain.borrow().left_motor.set_output(speeds.left)?;
+        self.drivetrain.borrow().right_motor.set_output(speeds.right)?;
         Ok(())
     }
 }
\ No newline at end of file
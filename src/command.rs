@@ -1,13 +1,22 @@
-use alloc::{boxed::Box, rc::Rc, vec::Vec};
+use alloc::{boxed::Box, rc::Rc, string::String, vec::Vec};
 use core::cell::RefCell;
 use pros::prelude::*;
 
-use crate::{CommandScheduler, AnySubsystem};
+use crate::{AnyCommand, CommandScheduler, AnySubsystem, ScheduleOutcome};
 
 /// An action the robot can perform. Runs when scheduled, until it is interrupted or it finishes.
 pub trait Command {
+    /// The subsystems this command requires exclusive access to.
+    /// No other command may require these subsystems while this command is running.
     fn get_requirements(&self) -> &[AnySubsystem];
 
+    /// The subsystems this command only reads from, such as sensors.
+    /// Any number of commands may share a subsystem, alongside the one command
+    /// requiring exclusive access to it.
+    fn get_shared_requirements(&self) -> &[AnySubsystem] {
+        &[]
+    }
+
     /// The initial subroutine of a command. Called once when the command is initially scheduled.
     fn initialize(&mut self) -> Result {
         Ok(())
@@ -31,16 +40,33 @@ pub trait Command {
     fn get_interruption_behavior(&self) -> InterruptionBehavior {
         InterruptionBehavior::default()
     }
+
+    /// A human-readable name for the command, used in logs and debug output.
+    /// Defaults to the name of the implementing type.
+    fn name(&self) -> &str {
+        core::any::type_name::<Self>()
+    }
+
+    /// Wraps the command so that [`Command::name`] returns the given name.
+    fn with_name(self, name: impl Into<String>) -> NamedCommand<Self>
+    where
+        Self: Sized,
+    {
+        NamedCommand {
+            command: self,
+            name: name.into(),
+        }
+    }
 }
 
 pub trait CommandRefExt {
-    fn schedule(&self) -> Result;
+    fn schedule(&self) -> Result<ScheduleOutcome>;
     fn cancel(&self) -> Result;
     fn is_scheduled(&self) -> bool;
 }
 
 impl CommandRefExt for Rc<RefCell<dyn Command>> {
-    fn schedule(&self) -> Result {
+    fn schedule(&self) -> Result<ScheduleOutcome> {
         CommandScheduler::schedule(self.clone())
     }
 
@@ -53,11 +79,153 @@ impl CommandRefExt for Rc<RefCell<dyn Command>> {
     }
 }
 
+/// Decides what happens when a command is scheduled that requires a subsystem
+/// already required by this command.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum InterruptionBehavior {
+    /// This command is cancelled in favor of the incoming command.
+    /// Equivalent to the lowest possible priority.
     #[default]
     CancelSelf,
+    /// The incoming command is rejected, regardless of its priority.
+    /// As an incoming command, this has the lowest possible priority.
     CancelIncoming,
+    /// This command is only interrupted by incoming commands of equal or higher priority.
+    Priority(i32),
+}
+
+impl InterruptionBehavior {
+    /// The numeric priority of the behavior, used when comparing it against a running command.
+    /// [`CancelIncoming`](Self::CancelIncoming) only protects a command once it is running,
+    /// so like [`CancelSelf`](Self::CancelSelf) it has the lowest possible priority.
+    pub const fn priority(self) -> i32 {
+        match self {
+            Self::CancelSelf | Self::CancelIncoming => i32::MIN,
+            Self::Priority(priority) => priority,
+        }
+    }
+
+    /// Returns true if a command with this behavior should be cancelled when
+    /// a command with the `incoming` behavior is scheduled.
+    pub const fn can_be_interrupted_by(self, incoming: Self) -> bool {
+        match self {
+            Self::CancelIncoming => false,
+            _ => self.priority() <= incoming.priority(),
+        }
+    }
+}
+
+/// A command decorator that overrides the name of the inner command.
+pub struct NamedCommand<C> {
+    command: C,
+    name: String,
+}
+
+impl<C: Command> Command for NamedCommand<C> {
+    fn get_requirements(&self) -> &[AnySubsystem] {
+        self.command.get_requirements()
+    }
+
+    fn get_shared_requirements(&self) -> &[AnySubsystem] {
+        self.command.get_shared_requirements()
+    }
+
+    fn initialize(&mut self) -> Result {
+        self.command.initialize()
+    }
+
+    fn execute(&mut self) -> Result {
+        self.command.execute()
+    }
+
+    fn end(&mut self, interrupted: bool) -> Result {
+        self.command.end(interrupted)
+    }
+
+    fn is_finished(&self) -> Result<bool> {
+        self.command.is_finished()
+    }
+
+    fn runs_when_disabled(&self) -> bool {
+        self.command.runs_when_disabled()
+    }
+
+    fn get_interruption_behavior(&self) -> InterruptionBehavior {
+        self.command.get_interruption_behavior()
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// A command decorator that restarts the inner command every time it finishes.
+/// Runs until it is interrupted.
+pub struct RepeatCommand {
+    command: AnyCommand,
+    name: String,
+    requirements: Vec<AnySubsystem>,
+    shared_requirements: Vec<AnySubsystem>,
+}
+
+impl RepeatCommand {
+    pub fn new(command: impl Into<AnyCommand>) -> Self {
+        let command = command.into();
+        let (name, requirements, shared_requirements) = {
+            let inner = command.borrow();
+            (
+                String::from(inner.name()),
+                inner.get_requirements().to_vec(),
+                inner.get_shared_requirements().to_vec(),
+            )
+        };
+        Self {
+            command,
+            name,
+            requirements,
+            shared_requirements,
+        }
+    }
+}
+
+impl Command for RepeatCommand {
+    fn get_requirements(&self) -> &[AnySubsystem] {
+        &self.requirements
+    }
+
+    fn get_shared_requirements(&self) -> &[AnySubsystem] {
+        &self.shared_requirements
+    }
+
+    fn initialize(&mut self) -> Result {
+        self.command.borrow_mut().initialize()
+    }
+
+    fn execute(&mut self) -> Result {
+        let mut command = self.command.borrow_mut();
+        command.execute()?;
+        if command.is_finished()? {
+            command.end(false)?;
+            command.initialize()?;
+        }
+        Ok(())
+    }
+
+    fn end(&mut self, interrupted: bool) -> Result {
+        self.command.borrow_mut().end(interrupted)
+    }
+
+    fn runs_when_disabled(&self) -> bool {
+        self.command.borrow().runs_when_disabled()
+    }
+
+    fn get_interruption_behavior(&self) -> InterruptionBehavior {
+        self.command.borrow().get_interruption_behavior()
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
 }
 
 pub struct FunctionalCommand {
@@ -66,6 +234,7 @@ pub struct FunctionalCommand {
     on_end: Box<dyn FnMut(bool) -> Result>,
     is_finished: Box<dyn Fn() -> Result<bool>>,
     requirements: Vec<AnySubsystem>,
+    shared_requirements: Vec<AnySubsystem>,
 }
 
 impl FunctionalCommand {
@@ -82,8 +251,15 @@ impl FunctionalCommand {
             on_end: Box::new(on_end),
             is_finished: Box::new(is_finished),
             requirements,
+            shared_requirements: Vec::new(),
         }
     }
+
+    /// Sets the subsystems this command only needs read-only access to.
+    pub fn with_shared_requirements(mut self, shared_requirements: Vec<AnySubsystem>) -> Self {
+        self.shared_requirements = shared_requirements;
+        self
+    }
 }
 
 impl Command for FunctionalCommand {
@@ -91,6 +267,10 @@ impl Command for FunctionalCommand {
         &self.requirements
     }
 
+    fn get_shared_requirements(&self) -> &[AnySubsystem] {
+        &self.shared_requirements
+    }
+
     fn initialize(&mut self) -> Result {
         (self.on_init)()
     }
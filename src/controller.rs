@@ -1,14 +1,88 @@
-use alloc::rc::Rc;
-use core::cell::RefCell;
+use alloc::{rc::Rc, vec::Vec};
+use core::{
+    cell::{Cell, RefCell},
+    time::Duration,
+};
+use pros::core::{os_task_local, time::Instant};
 use pros::devices::Controller;
-use pros::devices::controller::ControllerButton;
+use pros::devices::controller::{ControllerButton, JoystickAxis};
 use pros::prelude::*;
 
-use crate::{event::EventLoop, AnyCommand, CommandScheduler, command::CommandRefExt};
+use crate::{
+    command::{CommandRefExt, RepeatCommand},
+    event::{BindingHandle, EventLoop},
+    filter::{DebounceType, Debouncer},
+    AnyCommand, CommandScheduler, ScheduleOutcome,
+};
 
+type Condition = Rc<dyn Fn() -> bool>;
+type Bindings = Rc<RefCell<Vec<BindingHandle>>>;
+type Suppressors = Rc<RefCell<Vec<Rc<Suppressor>>>>;
+type RejectionHandler = Rc<dyn Fn(&AnyCommand, &ScheduleOutcome)>;
+
+/// Forces the triggers of a chord or sequence inactive while the chord or sequence is active.
+struct Suppressor {
+    /// Whether the chord or sequence currently suppresses its triggers.
+    active: Condition,
+    /// The bindings of the chord or sequence. Its triggers are only suppressed while one of
+    /// these is enabled.
+    bindings: Bindings,
+    /// The event loop the chord or sequence is polled on.
+    event_loop: Rc<RefCell<EventLoop>>,
+    /// The binding that samples `active` once per poll.
+    sampler: BindingHandle,
+}
+
+impl Suppressor {
+    fn is_active(&self) -> bool {
+        // The loop is mutably borrowed while it is being polled, so it must be enabled.
+        let loop_enabled = self
+            .event_loop
+            .try_borrow()
+            .map_or(true, |event_loop| event_loop.is_enabled());
+        loop_enabled
+            && self.bindings.borrow().iter().any(BindingHandle::is_enabled)
+            && (self.active)()
+    }
+
+    /// Returns true if the suppressor can never become active again, because every binding
+    /// of the chord or sequence was removed and the trigger itself was dropped.
+    fn is_dead(&self) -> bool {
+        Rc::strong_count(&self.bindings) == 1
+            && self.bindings.borrow().iter().all(BindingHandle::is_removed)
+    }
+}
+
+/// Removes dead suppressors from a list, along with their samplers.
+fn prune_suppressors(suppressors: &Suppressors) {
+    suppressors.borrow_mut().retain(|suppressor| {
+        if suppressor.is_dead() {
+            suppressor.sampler.remove();
+            return false;
+        }
+        true
+    });
+}
+
+/// A change in the state of a trigger, as seen by its bindings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edge {
+    Rising,
+    Falling,
+    /// The trigger became inactive because a chord or sequence it is part of suppressed it.
+    Suppressed,
+}
+
+#[derive(Clone)]
 pub struct Trigger {
     event_loop: Rc<RefCell<EventLoop>>,
-    condition: Rc<dyn Fn() -> bool>,
+    condition: Condition,
+    /// Chords and sequences that force this trigger inactive while they are active.
+    suppressors: Suppressors,
+    /// Handles to the bindings made on this trigger.
+    bindings: Bindings,
+    /// Called when the scheduler refuses a command scheduled by one of this trigger's bindings.
+    on_rejected: Option<RejectionHandler>,
 }
 
 impl Trigger {
@@ -19,144 +93,676 @@ impl Trigger {
         Self {
             event_loop,
             condition: Rc::new(condition),
+            suppressors: Default::default(),
+            bindings: Default::default(),
+            on_rejected: None,
         }
     }
 
     pub fn new(condition: impl Fn() -> bool + 'static) -> Self {
+        Self::new_with_loop(CommandScheduler::button_event_loop(), condition)
+    }
+
+    /// Creates a copy of this trigger whose bindings run on a different event loop,
+    /// such as one from [`CommandScheduler::event_loop`].
+    pub fn with_event_loop(&self, event_loop: Rc<RefCell<EventLoop>>) -> Self {
         Self {
-            event_loop: CommandScheduler::button_event_loop(),
-            condition: Rc::new(condition),
+            event_loop,
+            ..self.clone()
         }
     }
 
-    pub fn on_true(self, command: impl Into<AnyCommand>) -> Self {
-        let command = command.into();
-        let condition = self.condition.clone();
-        let mut pressed_last = condition();
-        self.event_loop.borrow_mut().bind(move || {
-            let pressed = condition();
-            if !pressed_last && pressed {
-                command.schedule().unwrap();
+    /// Creates a copy of this trigger whose bindings call `handler` with the command and
+    /// the [`ScheduleOutcome`] whenever the scheduler refuses a command they schedule,
+    /// for example to rumble the controller.
+    pub fn on_rejected(&self, handler: impl Fn(&AnyCommand, &ScheduleOutcome) + 'static) -> Self {
+        Self {
+            on_rejected: Some(Rc::new(handler)),
+            ..self.clone()
+        }
+    }
+
+    /// Schedules a command from a binding, reporting a refusal to the rejection handler.
+    fn schedule_fn(&self) -> impl Fn(&AnyCommand) -> Result + 'static {
+        let on_rejected = self.on_rejected.clone();
+        move |command| {
+            let outcome = command.schedule()?;
+            if let Some(on_rejected) = &on_rejected {
+                if !outcome.is_accepted() {
+                    on_rejected(command, &outcome);
+                }
             }
-            pressed_last = pressed;
             Ok(())
-        });
-        self
+        }
     }
 
-    pub fn on_false(self, command: impl Into<AnyCommand>) -> Self {
-        let command = command.into();
+    /// Returns true while a chord or sequence this trigger is part of suppresses it.
+    fn suppressed_fn(&self) -> impl Fn() -> bool + 'static {
+        let suppressors = self.suppressors.clone();
+        move || suppressors.borrow().iter().any(|suppressor| suppressor.is_active())
+    }
+
+    /// The condition of this trigger, which is inactive while the trigger is suppressed.
+    fn condition(&self) -> Condition {
         let condition = self.condition.clone();
-        let mut pressed_last = condition();
-        self.event_loop.borrow_mut().bind(move || {
-            let pressed = condition();
-            if pressed_last && !pressed {
-                command.schedule().unwrap();
+        let suppressed = self.suppressed_fn();
+        Rc::new(move || !suppressed() && condition())
+    }
+
+    /// Force the given triggers inactive while the suppressor is active.
+    fn suppress(triggers: &[Trigger], suppressor: Suppressor) {
+        let suppressor = Rc::new(suppressor);
+        for trigger in triggers {
+            prune_suppressors(&trigger.suppressors);
+            trigger.suppressors.borrow_mut().push(suppressor.clone());
+        }
+    }
+
+    /// Binds an action that runs whenever the state of this trigger changes.
+    fn bind_edges(&self, mut action: impl FnMut(Edge) -> Result + 'static) -> BindingHandle {
+        let condition = self.condition.clone();
+        let suppressed = self.suppressed_fn();
+        let mut active_last = !suppressed() && condition();
+        let handle = self.event_loop.borrow_mut().bind(move || {
+            let is_suppressed = suppressed();
+            let active = !is_suppressed && condition();
+            match (core::mem::replace(&mut active_last, active), active) {
+                (false, true) => action(Edge::Rising),
+                (true, false) if is_suppressed => action(Edge::Suppressed),
+                (true, false) => action(Edge::Falling),
+                _ => Ok(()),
             }
-            pressed_last = pressed;
-            Ok(())
         });
-        self
+        let mut bindings = self.bindings.borrow_mut();
+        bindings.retain(|binding| !binding.is_removed());
+        bindings.push(handle.clone());
+        handle
     }
 
-    pub fn while_true(self, command: impl Into<AnyCommand>) -> Self {
+    pub fn on_true(&self, command: impl Into<AnyCommand>) -> BindingHandle {
         let command = command.into();
-        let condition = self.condition.clone();
-        let mut pressed_last = condition();
-
-        self.event_loop.borrow_mut().bind(move || {
-            let pressed = condition();
-            if !pressed_last && pressed {
-                command.schedule().unwrap();
-            } else if pressed_last && !pressed {
-                command.cancel().unwrap();
+        let schedule = self.schedule_fn();
+        self.bind_edges(move |edge| {
+            if edge == Edge::Rising {
+                schedule(&command)?;
             }
-            pressed_last = pressed;
             Ok(())
-        });
-        self
+        })
     }
 
-    pub fn while_false(self, command: impl Into<AnyCommand>) -> Self {
+    pub fn on_false(&self, command: impl Into<AnyCommand>) -> BindingHandle {
         let command = command.into();
-        let condition = self.condition.clone();
-        let mut pressed_last = condition();
-
-        self.event_loop.borrow_mut().bind(move || {
-            let pressed = condition();
-            if pressed_last && !pressed {
-                command.schedule().unwrap();
-            } else if !pressed_last && pressed {
-                command.cancel().unwrap();
+        let schedule = self.schedule_fn();
+        self.bind_edges(move |edge| {
+            if edge == Edge::Falling {
+                schedule(&command)?;
             }
-            pressed_last = pressed;
             Ok(())
-        });
-        self
+        })
     }
 
-    pub fn toggle_on_true(self, command: impl Into<AnyCommand>) -> Self {
+    pub fn while_true(&self, command: impl Into<AnyCommand>) -> BindingHandle {
         let command = command.into();
-        let condition = self.condition.clone();
-        let mut pressed_last = condition();
+        let schedule = self.schedule_fn();
+        self.bind_edges(move |edge| match edge {
+            Edge::Rising => schedule(&command),
+            Edge::Falling | Edge::Suppressed => command.cancel(),
+        })
+    }
+
+    /// Schedules the command when the trigger becomes active, restarting it every time it
+    /// finishes, and cancels it when the trigger becomes inactive.
+    pub fn while_true_repeat(&self, command: impl Into<AnyCommand>) -> BindingHandle {
+        self.while_true(RepeatCommand::new(command))
+    }
+
+    pub fn while_false(&self, command: impl Into<AnyCommand>) -> BindingHandle {
+        let command = command.into();
+        let schedule = self.schedule_fn();
+        self.bind_edges(move |edge| match edge {
+            Edge::Falling | Edge::Suppressed => schedule(&command),
+            Edge::Rising => command.cancel(),
+        })
+    }
 
-        self.event_loop.borrow_mut().bind(move || {
-            let pressed = condition();
-            if !pressed_last && pressed {
+    /// Schedules the command whenever the trigger becomes active or inactive.
+    pub fn on_change(&self, command: impl Into<AnyCommand>) -> BindingHandle {
+        let command = command.into();
+        let schedule = self.schedule_fn();
+        self.bind_edges(move |edge| {
+            if edge != Edge::Suppressed {
+                schedule(&command)?;
+            }
+            Ok(())
+        })
+    }
+
+    pub fn toggle_on_true(&self, command: impl Into<AnyCommand>) -> BindingHandle {
+        let command = command.into();
+        let schedule = self.schedule_fn();
+        self.bind_edges(move |edge| {
+            if edge == Edge::Rising {
                 if command.is_scheduled() {
-                    command.cancel().unwrap();
+                    command.cancel()?;
                 } else {
-                    command.schedule().unwrap();
+                    schedule(&command)?;
                 }
             }
-            pressed_last = pressed;
             Ok(())
-        });
-        self
+        })
     }
 
-    pub fn toggle_on_false(self, command: impl Into<AnyCommand>) -> Self {
+    pub fn toggle_on_false(&self, command: impl Into<AnyCommand>) -> BindingHandle {
         let command = command.into();
-        let condition = self.condition.clone();
-        let mut pressed_last = condition();
-
-        self.event_loop.borrow_mut().bind(move || {
-            let pressed = condition();
-            if pressed_last && !pressed {
+        let schedule = self.schedule_fn();
+        self.bind_edges(move |edge| {
+            if edge == Edge::Falling {
                 if command.is_scheduled() {
-                    command.cancel().unwrap();
+                    command.cancel()?;
                 } else {
-                    command.schedule().unwrap();
+                    schedule(&command)?;
                 }
             }
-            pressed_last = pressed;
             Ok(())
-        });
-        self
+        })
     }
 
     pub fn is_active(&self) -> bool {
-        (self.condition)()
+        (self.condition())()
     }
 
     pub fn and(&self, other: &Self) -> Self {
-        let condition = self.condition.clone();
-        let other_condition = other.condition.clone();
-        Self::new(move || condition() && other_condition())
+        let condition = self.condition();
+        let other_condition = other.condition();
+        Self::new_with_loop(self.event_loop.clone(), move || condition() && other_condition())
     }
 
     pub fn or(&self, other: &Self) -> Self {
-        let condition = self.condition.clone();
-        let other_condition = other.condition.clone();
-        Self::new(move || condition() || other_condition())
+        let condition = self.condition();
+        let other_condition = other.condition();
+        Self::new_with_loop(self.event_loop.clone(), move || condition() || other_condition())
     }
 
     pub fn negate(&self) -> Self {
-        let condition = self.condition.clone();
-        Self::new(move || !condition())
+        let condition = self.condition();
+        Self::new_with_loop(self.event_loop.clone(), move || !condition())
     }
 
-    pub fn button(controller: Controller, button: ControllerButton) -> Result<Self> {
-        Ok(Self::new(move || controller.button(button).expect("Expected controller to be available")))
+    /// Creates a trigger whose state is computed by `update` exactly once per poll of the
+    /// event loop, so that stateful filters see every sample exactly once.
+    ///
+    /// The state is computed the first time it is read in each poll, so the result does not
+    /// depend on the order in which bindings were added to the loop. Reading it outside of a
+    /// poll returns the state from the last poll.
+    fn polled(
+        event_loop: Rc<RefCell<EventLoop>>,
+        update: impl FnMut() -> bool + 'static,
+    ) -> (Self, BindingHandle) {
+        let generation = event_loop.borrow().generation();
+        let evaluated_at = Cell::new(None);
+        let state = Cell::new(false);
+        let update = RefCell::new(update);
+        let get: Condition = Rc::new(move || {
+            if evaluated_at.get() != Some(generation.get()) {
+                evaluated_at.set(Some(generation.get()));
+                state.set((update.borrow_mut())());
+            }
+            state.get()
+        });
+        let sampler = event_loop.borrow_mut().bind({
+            let get = get.clone();
+            move || {
+                get();
+                Ok(())
+            }
+        });
+        (Self::new_with_loop(event_loop, move || get()), sampler)
+    }
+
+    /// Creates a trigger whose state is computed from this trigger's state once per poll of
+    /// the event loop.
+    fn filtered(&self, mut filter: impl FnMut(bool) -> bool + 'static) -> Self {
+        let condition = self.condition();
+        Self::polled(self.event_loop.clone(), move || filter(condition())).0
+    }
+
+    /// Creates a trigger that only changes state once this trigger has held
+    /// its new state for the given duration.
+    pub fn debounce(&self, duration: Duration, debounce_type: DebounceType) -> Self {
+        let mut debouncer = Debouncer::new(duration, debounce_type);
+        self.filtered(move |active| debouncer.calculate(active))
+    }
+
+    /// Creates a trigger that is active once this trigger has been continuously
+    /// active for the given duration, until this trigger becomes inactive.
+    pub fn long_press(&self, duration: Duration) -> Self {
+        let mut pressed_since = None;
+        self.filtered(move |pressed| {
+            if !pressed {
+                pressed_since = None;
+                return false;
+            }
+            let pressed_since = *pressed_since.get_or_insert_with(Instant::now);
+            pressed_since.elapsed() >= duration
+        })
+    }
+
+    /// Creates a trigger that is active while this trigger is held for a second time,
+    /// if the second press started within `window` of the first one.
+    pub fn double_tap(&self, window: Duration) -> Self {
+        self.multi_press(2, window)
+    }
+
+    /// Creates a trigger that is active while this trigger is held for the `presses`th time,
+    /// if all of the presses started within `window` of the first one.
+    pub fn multi_press(&self, presses: usize, window: Duration) -> Self {
+        let mut count = 0;
+        let mut first_press: Option<Instant> = None;
+        let mut pressed_last = false;
+        let mut active = false;
+        self.filtered(move |pressed| {
+            let was_pressed = core::mem::replace(&mut pressed_last, pressed);
+            if active {
+                active = pressed;
+                return active;
+            }
+
+            if first_press.is_some_and(|first_press| first_press.elapsed() > window) {
+                count = 0;
+                first_press = None;
+            }
+
+            if !was_pressed && pressed {
+                first_press.get_or_insert_with(Instant::now);
+                count += 1;
+                if count >= presses {
+                    count = 0;
+                    first_press = None;
+                    active = true;
+                }
+            }
+            active
+        })
+    }
+
+    /// Creates a trigger that is active while all of the given triggers are active.
+    ///
+    /// Unlike [`Trigger::and`], the individual triggers are forced inactive from the moment
+    /// the chord becomes active until all of them have been released. Commands started by
+    /// their `while_true` bindings are cancelled, but their edge bindings such as `on_false`
+    /// and `on_change` do not run, neither when the chord is completed nor when it is released.
+    ///
+    /// The triggers are only suppressed while one of the chord's own bindings is enabled and
+    /// its event loop is enabled, so removing the chord's bindings ends the suppression.
+    pub fn chord(triggers: &[Trigger]) -> Self {
+        let conditions = triggers
+            .iter()
+            .map(|trigger| trigger.condition.clone())
+            .collect::<Vec<_>>();
+        let event_loop = triggers
+            .first()
+            .map_or_else(CommandScheduler::button_event_loop, |trigger| {
+                trigger.event_loop.clone()
+            });
+
+        let chord = Self::new_with_loop(event_loop.clone(), {
+            let conditions = conditions.clone();
+            move || !conditions.is_empty() && conditions.iter().all(|condition| condition())
+        });
+
+        let mut latched = false;
+        let (latch, sampler) = Self::polled(event_loop.clone(), move || {
+            let all = conditions.iter().all(|condition| condition());
+            let any = conditions.iter().any(|condition| condition());
+            latched = all || (latched && any);
+            latched
+        });
+        Self::suppress(
+            triggers,
+            Suppressor {
+                active: latch.condition,
+                bindings: chord.bindings.clone(),
+                event_loop,
+                sampler,
+            },
+        );
+        chord
+    }
+
+    /// Creates a trigger that becomes active when the given triggers are pressed in order,
+    /// each within `window` of the previous one, and stays active while the last one is held.
+    ///
+    /// The last trigger is forced inactive while the sequence is active, in the same way as
+    /// the triggers of a [chord](Trigger::chord), so its own bindings do not run when it
+    /// completes the sequence.
+    pub fn sequence(triggers: &[Trigger], window: Duration) -> Self {
+        let conditions = triggers
+            .iter()
+            .map(|trigger| trigger.condition.clone())
+            .collect::<Vec<_>>();
+        let event_loop = triggers
+            .first()
+            .map_or_else(CommandScheduler::button_event_loop, |trigger| {
+                trigger.event_loop.clone()
+            });
+
+        let mut state = SequenceState {
+            pressed_last: conditions.iter().map(|condition| condition()).collect(),
+            step: 0,
+            last_step: None,
+            active: false,
+        };
+        let (sequence, sampler) =
+            Self::polled(event_loop.clone(), move || state.update(&conditions, window));
+        Self::suppress(
+            &triggers[triggers.len().saturating_sub(1)..],
+            Suppressor {
+                active: sequence.condition.clone(),
+                bindings: sequence.bindings.clone(),
+                event_loop,
+                sampler,
+            },
+        );
+        sequence
+    }
+
+    /// Creates a trigger that is active while the given button is pressed.
+    /// A disconnected controller is treated as having no buttons pressed.
+    ///
+    /// Every trigger for the same button shares its suppressors, so a [chord](Trigger::chord)
+    /// suppresses the button's bindings no matter which of its triggers they were made from.
+    pub fn button(controller: Controller, button: ControllerButton) -> Self {
+        let suppressors = BUTTON_SUPPRESSORS.with(|buttons| {
+            let mut buttons = buttons.borrow_mut();
+            let id = controller as u32;
+            if let Some((_, _, suppressors)) = buttons
+                .iter()
+                .find(|(other_id, other_button, _)| *other_id == id && *other_button == button)
+            {
+                prune_suppressors(suppressors);
+                return suppressors.clone();
+            }
+            let suppressors = Suppressors::default();
+            buttons.push((id, button, suppressors.clone()));
+            suppressors
+        });
+        Self {
+            suppressors,
+            ..Self::new(move || controller.button(button).unwrap_or(false))
+        }
+    }
+}
+
+os_task_local! {
+    /// The suppressors of each controller button, keyed by controller id and button.
+    static BUTTON_SUPPRESSORS: RefCell<Vec<(u32, ControllerButton, Suppressors)>> =
+        RefCell::new(Vec::new());
+}
+
+/// A wrapper around a [`Controller`] that exposes its buttons and joysticks as [`Trigger`]s.
+/// A disconnected controller is treated as having every button released and every joystick centered.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CommandController {
+    controller: Controller,
+}
+
+impl CommandController {
+    pub const fn new(controller: Controller) -> Self {
+        Self { controller }
+    }
+
+    /// The underlying controller.
+    pub const fn controller(&self) -> Controller {
+        self.controller
+    }
+
+    /// Creates a trigger that is active while the given button is pressed.
+    pub fn button(&self, button: ControllerButton) -> Trigger {
+        Trigger::button(self.controller, button)
+    }
+
+    pub fn a(&self) -> Trigger {
+        self.button(ControllerButton::A)
+    }
+
+    pub fn b(&self) -> Trigger {
+        self.button(ControllerButton::B)
+    }
+
+    pub fn x(&self) -> Trigger {
+        self.button(ControllerButton::X)
+    }
+
+    pub fn y(&self) -> Trigger {
+        self.button(ControllerButton::Y)
+    }
+
+    pub fn up(&self) -> Trigger {
+        self.button(ControllerButton::Up)
+    }
+
+    pub fn down(&self) -> Trigger {
+        self.button(ControllerButton::Down)
+    }
+
+    pub fn left(&self) -> Trigger {
+        self.button(ControllerButton::Left)
+    }
+
+    pub fn right(&self) -> Trigger {
+        self.button(ControllerButton::Right)
+    }
+
+    pub fn l1(&self) -> Trigger {
+        self.button(ControllerButton::LeftTrigger1)
+    }
+
+    pub fn l2(&self) -> Trigger {
+        self.button(ControllerButton::LeftTrigger2)
+    }
+
+    pub fn r1(&self) -> Trigger {
+        self.button(ControllerButton::RightTrigger1)
+    }
+
+    pub fn r2(&self) -> Trigger {
+        self.button(ControllerButton::RightTrigger2)
+    }
+
+    /// A joystick axis of the controller.
+    pub const fn axis(&self, axis: JoystickAxis) -> ControllerAxis {
+        ControllerAxis {
+            controller: self.controller,
+            axis,
+        }
+    }
+
+    pub const fn left_x(&self) -> ControllerAxis {
+        self.axis(JoystickAxis::LeftX)
+    }
+
+    pub const fn left_y(&self) -> ControllerAxis {
+        self.axis(JoystickAxis::LeftY)
+    }
+
+    pub const fn right_x(&self) -> ControllerAxis {
+        self.axis(JoystickAxis::RightX)
+    }
+
+    pub const fn right_y(&self) -> ControllerAxis {
+        self.axis(JoystickAxis::RightY)
+    }
+}
+
+/// A single joystick axis of a [`CommandController`], from -1.0 to 1.0.
+#[derive(Debug, Clone, Copy)]
+pub struct ControllerAxis {
+    controller: Controller,
+    axis: JoystickAxis,
+}
+
+impl ControllerAxis {
+    /// The current value of the axis, or 0.0 if the controller is disconnected.
+    pub fn value(&self) -> f32 {
+        self.controller.joystick_axis(self.axis).unwrap_or(0.0)
+    }
+
+    /// Creates a trigger that is active while the axis is greater than `threshold`.
+    pub fn above(self, threshold: f32) -> Trigger {
+        AboveTrigger::new(move || self.value(), threshold).into()
+    }
+
+    /// Creates a trigger that activates when the axis rises above `activate`
+    /// and stays active until it falls below `release`.
+    pub fn above_with_release(self, activate: f32, release: f32) -> Trigger {
+        AboveTrigger::new(move || self.value(), activate)
+            .release_below(release)
+            .into()
+    }
+
+    /// Creates a trigger that is active while the axis is less than `threshold`.
+    pub fn below(self, threshold: f32) -> Trigger {
+        BelowTrigger::new(move || self.value(), threshold).into()
+    }
+
+    /// Creates a trigger that activates when the axis falls below `activate`
+    /// and stays active until it rises above `release`.
+    pub fn below_with_release(self, activate: f32, release: f32) -> Trigger {
+        BelowTrigger::new(move || self.value(), activate)
+            .release_above(release)
+            .into()
+    }
+}
+
+/// A builder for a [`Trigger`] that activates when an analog value rises above a threshold.
+///
+/// By default the trigger is released as soon as the value falls back to the threshold.
+/// Setting a lower release threshold adds a hysteresis band, which keeps noisy values
+/// hovering around the threshold from toggling the trigger every loop.
+pub struct AboveTrigger {
+    value: Rc<dyn Fn() -> f32>,
+    activate: f32,
+    release: f32,
+}
+
+impl AboveTrigger {
+    pub fn new(value: impl Fn() -> f32 + 'static, threshold: f32) -> Self {
+        Self {
+            value: Rc::new(value),
+            activate: threshold,
+            release: threshold,
+        }
+    }
+
+    /// Keep the trigger active until the value falls below `threshold`.
+    pub fn release_below(mut self, threshold: f32) -> Self {
+        self.release = threshold;
+        self
+    }
+}
+
+impl From<AboveTrigger> for Trigger {
+    fn from(trigger: AboveTrigger) -> Self {
+        let active = Cell::new(false);
+        Self::new(move || {
+            let value = (trigger.value)();
+            let is_active = if active.get() {
+                value >= trigger.release
+            } else {
+                value > trigger.activate
+            };
+            active.set(is_active);
+            is_active
+        })
+    }
+}
+
+/// A builder for a [`Trigger`] that activates when an analog value falls below a threshold.
+///
+/// By default the trigger is released as soon as the value rises back to the threshold.
+/// Setting a higher release threshold adds a hysteresis band.
+pub struct BelowTrigger {
+    value: Rc<dyn Fn() -> f32>,
+    activate: f32,
+    release: f32,
+}
+
+impl BelowTrigger {
+    pub fn new(value: impl Fn() -> f32 + 'static, threshold: f32) -> Self {
+        Self {
+            value: Rc::new(value),
+            activate: threshold,
+            release: threshold,
+        }
+    }
+
+    /// Keep the trigger active until the value rises above `threshold`.
+    pub fn release_above(mut self, threshold: f32) -> Self {
+        self.release = threshold;
+        self
+    }
+}
+
+impl From<BelowTrigger> for Trigger {
+    fn from(trigger: BelowTrigger) -> Self {
+        let active = Cell::new(false);
+        Self::new(move || {
+            let value = (trigger.value)();
+            let is_active = if active.get() {
+                value <= trigger.release
+            } else {
+                value < trigger.activate
+            };
+            active.set(is_active);
+            is_active
+        })
+    }
+}
+
+/// Progress through the triggers of a [`Trigger::sequence`].
+struct SequenceState {
+    pressed_last: Vec<bool>,
+    /// The index of the next trigger that must be pressed.
+    step: usize,
+    last_step: Option<Instant>,
+    active: bool,
+}
+
+impl SequenceState {
+    fn update(&mut self, conditions: &[Condition], window: Duration) -> bool {
+        let pressed = conditions.iter().map(|condition| condition()).collect::<Vec<_>>();
+        let pressed_last = core::mem::replace(&mut self.pressed_last, pressed.clone());
+
+        if self.active {
+            self.active = pressed.last().copied().unwrap_or(false);
+            return self.active;
+        }
+
+        if self.last_step.is_some_and(|last_step| last_step.elapsed() > window) {
+            self.step = 0;
+            self.last_step = None;
+        }
+
+        let rising = |i: usize| pressed[i] && !pressed_last[i];
+        if (0..pressed.len()).any(rising) {
+            if self.step < pressed.len() && rising(self.step) {
+                self.step += 1;
+                self.last_step = Some(Instant::now());
+            } else if rising(0) {
+                // Pressing the wrong trigger restarts the sequence.
+                self.step = 1;
+                self.last_step = Some(Instant::now());
+            } else {
+                self.step = 0;
+                self.last_step = None;
+            }
+        }
+
+        if !pressed.is_empty() && self.step == pressed.len() {
+            self.step = 0;
+            self.last_step = None;
+            self.active = true;
+        }
+        self.active
     }
 }
@@ -1,121 +1,385 @@
 use alloc::{boxed::Box, rc::Rc, vec::Vec};
-use core::cell::{Cell, RefCell};
-use crate::controller::Trigger;
+use core::{
+    cell::{Cell, RefCell},
+    time::Duration,
+};
+use crate::{
+    controller::Trigger,
+    filter::{DebounceType, Debouncer},
+};
+use pros::core::time::Instant;
 use pros::prelude::*;
 
+#[derive(Debug, Default)]
+struct BindingState {
+    disabled: Cell<bool>,
+    removed: Cell<bool>,
+}
+
+/// A handle to an event bound to an [`EventLoop`], used to remove or pause it.
+#[derive(Debug, Clone)]
+pub struct BindingHandle {
+    state: Rc<BindingState>,
+}
+
+impl BindingHandle {
+    /// Remove the event from its loop. It will never run again.
+    pub fn remove(&self) {
+        self.state.removed.set(true);
+    }
+
+    /// Stop running the event when the loop is polled, until [`BindingHandle::enable`] is called.
+    ///
+    /// Bindings that watch for edges do not see changes that happen while they are disabled,
+    /// so an edge may be detected on the first poll after the binding is re-enabled.
+    pub fn disable(&self) {
+        self.state.disabled.set(true);
+    }
+
+    pub fn enable(&self) {
+        self.state.disabled.set(false);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.state.disabled.get() && !self.state.removed.get()
+    }
+
+    pub fn is_removed(&self) -> bool {
+        self.state.removed.get()
+    }
+}
+
+struct Binding {
+    state: Rc<BindingState>,
+    action: Box<dyn FnMut() -> Result>,
+}
+
 #[derive(Default)]
 pub struct EventLoop {
-    events: Vec<Box<dyn FnMut() -> Result>>,
+    events: Vec<Binding>,
+    disabled: bool,
+    /// Incremented on every poll, so that [`BooleanEvent`]s are evaluated at most once per poll.
+    generation: Rc<Cell<u64>>,
 }
 
 impl EventLoop {
     /// Add an event to run when the loop is polled.
-    pub fn bind(&mut self, action: impl FnMut() -> Result + 'static) {
-        self.events.push(Box::new(action));
+    pub fn bind(&mut self, action: impl FnMut() -> Result + 'static) -> BindingHandle {
+        let state = Rc::new(BindingState::default());
+        self.events.push(Binding {
+            state: state.clone(),
+            action: Box::new(action),
+        });
+        BindingHandle { state }
+    }
+
+    /// A counter that is incremented every time the loop is polled.
+    pub(crate) fn generation(&self) -> Rc<Cell<u64>> {
+        self.generation.clone()
     }
 
+    /// Run every bound event once. Does nothing while the loop is disabled.
+    ///
+    /// An error from one event does not prevent the remaining events from running;
+    /// the first error encountered is returned once all events have been polled.
     pub fn poll(&mut self) -> Result {
+        if self.disabled {
+            return Ok(());
+        }
+        self.generation.set(self.generation.get() + 1);
+
+        self.events.retain(|event| !event.state.removed.get());
+
+        let mut result = Ok(());
         for event in self.events.iter_mut() {
-            event()?;
+            if event.state.disabled.get() || event.state.removed.get() {
+                continue;
+            }
+            if let Err(err) = (event.action)() {
+                if result.is_ok() {
+                    result = Err(err);
+                }
+            }
         }
-        Ok(())
+        result
     }
 
+    /// Remove every binding, as if [`BindingHandle::remove`] was called on each of them.
     pub fn clear(&mut self) {
-        self.events.clear();
+        for event in self.events.drain(..) {
+            event.state.removed.set(true);
+        }
+    }
+
+    /// Stop running events when the loop is polled, until [`EventLoop::enable`] is called.
+    pub fn disable(&mut self) {
+        self.disabled = true;
+    }
+
+    pub fn enable(&mut self) {
+        self.disabled = false;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.disabled
     }
 }
 
+type Evaluate = Box<dyn FnMut() -> Result<bool>>;
+
+/// The value of a [`BooleanEvent`], computed lazily at most once per poll of its event loop.
+struct Signal {
+    generation: Rc<Cell<u64>>,
+    evaluated_at: Cell<Option<u64>>,
+    current: Cell<bool>,
+    previous: Cell<bool>,
+    evaluate: RefCell<Evaluate>,
+    /// An evaluation error that has not been returned from [`Signal::get`] yet.
+    error: RefCell<Option<Box<dyn core::error::Error>>>,
+}
+
+impl Signal {
+    /// Evaluates the signal if it has not been evaluated during the current poll.
+    /// If evaluation fails, the signal keeps its last value until the next poll.
+    fn evaluate(&self) {
+        let generation = self.generation.get();
+        if self.evaluated_at.get() == Some(generation) {
+            return;
+        }
+        self.evaluated_at.set(Some(generation));
+        self.previous.set(self.current.get());
+        match (self.evaluate.borrow_mut())() {
+            Ok(value) => self.current.set(value),
+            Err(err) => *self.error.borrow_mut() = Some(err),
+        }
+    }
+
+    /// The value of the signal for the current poll, evaluating it if it has not been yet.
+    /// An evaluation error is returned to the first caller only.
+    fn get(&self) -> Result<bool> {
+        self.evaluate();
+        match self.error.take() {
+            Some(err) => Err(err),
+            None => Ok(self.current.get()),
+        }
+    }
+
+    /// Like [`Signal::get`], but leaves any error to be reported by the event loop.
+    fn value(&self) -> bool {
+        self.evaluate();
+        self.current.get()
+    }
+}
+
+/// A boolean signal that is sampled once per poll of an event loop.
+///
+/// Events derived from other events evaluate their sources first, on demand, so the
+/// result does not depend on the order in which events were bound to the loop.
 pub struct BooleanEvent {
     event_loop: Rc<RefCell<EventLoop>>,
-    state: Rc<Cell<bool>>,
+    signal: Rc<Signal>,
 }
 
 impl BooleanEvent {
+    /// Creates an event from a signal. The signal is sampled once immediately,
+    /// and then once per poll of the event loop.
     pub fn new(
         event_loop: Rc<RefCell<EventLoop>>,
         mut signal: impl FnMut() -> Result<bool> + 'static,
     ) -> Result<Self> {
-        let state = Rc::new(Cell::new(signal()?));
+        let initial = signal()?;
+        let generation = event_loop.borrow().generation.clone();
+        let signal = Rc::new(Signal {
+            evaluated_at: Cell::new(Some(generation.get())),
+            generation,
+            current: Cell::new(initial),
+            previous: Cell::new(initial),
+            evaluate: RefCell::new(Box::new(signal)),
+            error: RefCell::new(None),
+        });
         event_loop.borrow_mut().bind({
-            let state = state.clone();
+            let signal = signal.clone();
             move || {
-                state.set(signal()?);
+                signal.get()?;
                 Ok(())
             }
         });
-        Ok(Self { event_loop, state })
+        Ok(Self { event_loop, signal })
     }
 
+    /// Creates an event on the same event loop as this one.
+    fn derive(&self, signal: impl FnMut() -> Result<bool> + 'static) -> Result<Self> {
+        Self::new(self.event_loop.clone(), signal)
+    }
+
+    /// The value of the event as of the last time it was sampled.
     pub fn current_state(&self) -> bool {
-        self.state.get()
+        self.signal.current.get()
     }
 
-    pub fn if_high(&self, mut action: impl FnMut() -> Result + 'static) {
-        let state = self.state.clone();
+    pub fn if_high(&self, mut action: impl FnMut() -> Result + 'static) -> BindingHandle {
+        let signal = self.signal.clone();
         self.event_loop.borrow_mut().bind(move || {
-            if state.get() {
+            if signal.get()? {
                 action()
             } else {
                 Ok(())
             }
-        });
+        })
     }
 
-    pub fn rising(&self) -> Self {
-        let mut previous = self.state.get();
-        let state = self.state.clone();
+    /// Creates an event that is true for one poll when this event changes from false to true.
+    pub fn rising(&self) -> Result<Self> {
+        let signal = self.signal.clone();
+        self.derive(move || Ok(signal.get()? && !signal.previous.get()))
+    }
+
+    /// Creates an event that is true for one poll when this event changes from true to false.
+    pub fn falling(&self) -> Result<Self> {
+        let signal = self.signal.clone();
+        self.derive(move || Ok(!signal.get()? && signal.previous.get()))
+    }
+
+    pub fn negate(&self) -> Result<Self> {
+        let signal = self.signal.clone();
+        self.derive(move || Ok(!signal.get()?))
+    }
 
-        Self::new(self.event_loop.clone(), move || {
-            let present = state.get();
-            let is_rising = !previous && present;
-            previous = present;
-            Ok(is_rising)
-        }).unwrap()
+    pub fn and(&self, other: &Self) -> Result<Self> {
+        let signal = self.signal.clone();
+        let other_signal = other.signal.clone();
+        // Both sides are always evaluated so that neither misses a sample.
+        self.derive(move || Ok(signal.get()? & other_signal.get()?))
     }
 
-    pub fn falling(&self) -> Self {
-        let mut previous = self.state.get();
-        let state = self.state.clone();
+    pub fn or(&self, other: &Self) -> Result<Self> {
+        let signal = self.signal.clone();
+        let other_signal = other.signal.clone();
+        self.derive(move || Ok(signal.get()? | other_signal.get()?))
+    }
+
+    pub fn xor(&self, other: &Self) -> Result<Self> {
+        let signal = self.signal.clone();
+        let other_signal = other.signal.clone();
+        self.derive(move || Ok(signal.get()? ^ other_signal.get()?))
+    }
+
+    /// Creates an event that is true for one poll whenever this event changes value.
+    pub fn changed(&self) -> Result<Self> {
+        let signal = self.signal.clone();
+        self.derive(move || Ok(signal.get()? != signal.previous.get()))
+    }
+
+    /// Run `action` with the new value whenever this event changes value.
+    pub fn on_change(&self, mut action: impl FnMut(bool) -> Result + 'static) -> BindingHandle {
+        let signal = self.signal.clone();
+        self.event_loop.borrow_mut().bind(move || {
+            let value = signal.get()?;
+            if value != signal.previous.get() {
+                action(value)
+            } else {
+                Ok(())
+            }
+        })
+    }
 
-        Self::new(self.event_loop.clone(), move || {
-            let present = state.get();
-            let is_falling = previous && !present;
-            previous = present;
-            Ok(is_falling)
-        }).unwrap()
+    /// Creates an SR flip-flop that becomes true when `set` is true and stays true
+    /// until `reset` is true. If both are true at once, `reset` wins.
+    pub fn latched(set: &Self, reset: &Self) -> Result<Self> {
+        let set_signal = set.signal.clone();
+        let reset_signal = reset.signal.clone();
+        let mut latched = false;
+        set.derive(move || {
+            let set = set_signal.get()?;
+            let reset = reset_signal.get()?;
+            latched = !reset && (set || latched);
+            Ok(latched)
+        })
     }
 
-    pub fn negate(&self) -> Self {
-        let state = self.state.clone();
-        Self::new(self.event_loop.clone(), move || Ok(!state.get())).unwrap()
+    /// Counts the number of times this event changes from false to true.
+    pub fn count_rising(&self) -> EventCounter {
+        let signal = self.signal.clone();
+        let count = Rc::new(Cell::new(0));
+        self.event_loop.borrow_mut().bind({
+            let count = count.clone();
+            move || {
+                if signal.get()? && !signal.previous.get() {
+                    count.set(count.get() + 1);
+                }
+                Ok(())
+            }
+        });
+        EventCounter { count }
     }
 
-    pub fn and(&self, other: &Self) -> Self {
-        let state = self.state.clone();
-        let other_state = other.state.clone();
-        Self::new(self.event_loop.clone(), move || {
-            Ok(state.get() && other_state.get())
-        }).unwrap()
+    /// Measures how long this event has been continuously true.
+    pub fn duration_true(&self) -> EventTimer {
+        let signal = self.signal.clone();
+        let since = Rc::new(Cell::new(self.current_state().then(Instant::now)));
+        self.event_loop.borrow_mut().bind({
+            let since = since.clone();
+            move || {
+                if !signal.get()? {
+                    since.set(None);
+                } else if since.get().is_none() {
+                    since.set(Some(Instant::now()));
+                }
+                Ok(())
+            }
+        });
+        EventTimer { since }
     }
 
-    pub fn or(&self, other: &Self) -> Self {
-        let state = self.state.clone();
-        let other_state = other.state.clone();
-        Self::new(self.event_loop.clone(), move || {
-            Ok(state.get() || other_state.get())
-        }).unwrap()
+    /// Creates an event that only changes state once this event has held
+    /// its new state for the given duration.
+    pub fn debounce(&self, duration: Duration, debounce_type: DebounceType) -> Result<Self> {
+        let signal = self.signal.clone();
+        let mut debouncer = Debouncer::new(duration, debounce_type);
+        self.derive(move || Ok(debouncer.calculate(signal.get()?)))
     }
 
     pub fn as_trigger(&self) -> Trigger {
-        let state = self.state.clone();
-        Trigger::new_with_loop(self.event_loop.clone(), move || state.get())
+        let signal = self.signal.clone();
+        // Errors are reported when the event loop samples the signal.
+        Trigger::new_with_loop(self.event_loop.clone(), move || signal.value())
+    }
+}
+
+/// The number of rising edges of a [`BooleanEvent`], from [`BooleanEvent::count_rising`].
+#[derive(Debug, Clone)]
+pub struct EventCounter {
+    count: Rc<Cell<u32>>,
+}
+
+impl EventCounter {
+    pub fn count(&self) -> u32 {
+        self.count.get()
+    }
+
+    pub fn reset(&self) {
+        self.count.set(0);
+    }
+}
+
+/// How long a [`BooleanEvent`] has been true, from [`BooleanEvent::duration_true`].
+#[derive(Debug, Clone)]
+pub struct EventTimer {
+    since: Rc<Cell<Option<Instant>>>,
+}
+
+impl EventTimer {
+    /// The time since the event became true, or zero if it is currently false.
+    pub fn elapsed(&self) -> Duration {
+        self.since
+            .get()
+            .map_or(Duration::ZERO, |since| since.elapsed())
     }
 }
 
 impl From<BooleanEvent> for Trigger {
     fn from(event: BooleanEvent) -> Self {
-        Self::new_with_loop(event.event_loop, move || event.state.get())
+        event.as_trigger()
     }
 }
 
@@ -0,0 +1,231 @@
+use alloc::{boxed::Box, collections::VecDeque, ffi::CString, format, string::String, vec::Vec};
+use core::time::Duration;
+
+use pros::core::time::Instant;
+use pros::devices::{battery, controller::ControllerLine, Controller};
+use pros::prelude::*;
+
+use crate::{command::Command, AnySubsystem, CommandScheduler};
+
+/// The minimum time between two writes to the same controller.
+/// The controller drops writes that are sent faster than this.
+pub const CONTROLLER_WRITE_PERIOD: Duration = Duration::from_millis(50);
+
+/// The number of lines on the controller screen.
+const LINES: usize = ControllerLine::MAX_LINE_NUM as usize + 1;
+
+#[derive(Debug, Default)]
+struct ControllerQueue {
+    /// The latest text requested for each line that has not been written yet.
+    pending_lines: [Option<String>; LINES],
+    /// The text last written to each line.
+    shown_lines: [Option<String>; LINES],
+    rumbles: VecDeque<String>,
+    next_line: usize,
+    last_write: Option<Instant>,
+}
+
+impl ControllerQueue {
+    fn flush(&mut self, controller: Controller) {
+        if self
+            .last_write
+            .is_some_and(|last_write| last_write.elapsed() < CONTROLLER_WRITE_PERIOD)
+        {
+            return;
+        }
+
+        let id = controller as pros::sys::controller_id_e_t;
+        // SAFETY: `id` is a valid controller id.
+        if unsafe { pros::sys::controller_is_connected(id) } != 1 {
+            // Nothing is shown on a disconnected controller, so every line is rewritten
+            // once it reconnects. Rumbles are dropped rather than played late.
+            self.shown_lines = Default::default();
+            self.rumbles.clear();
+            return;
+        }
+
+        if let Some(pattern) = self.rumbles.pop_front() {
+            let pattern = CString::new(pattern).unwrap_or_default();
+            // SAFETY: `pattern` is a valid null-terminated string that outlives the call.
+            unsafe { pros::sys::controller_rumble(id, pattern.as_ptr()) };
+            self.last_write = Some(Instant::now());
+            return;
+        }
+
+        for offset in 0..LINES {
+            let line = (self.next_line + offset) % LINES;
+            let Some(text) = self.pending_lines[line].take() else {
+                continue;
+            };
+            let c_text = CString::new(text.clone()).unwrap_or_default();
+            // SAFETY: `id` is a valid controller id, `line` is less than `LINES` and
+            // `c_text` is a valid null-terminated string that outlives the call.
+            let result =
+                unsafe { pros::sys::controller_set_text(id, line as u8, 0, c_text.as_ptr()) };
+            if result == pros::sys::PROS_ERR {
+                // Retry on a later flush.
+                self.pending_lines[line] = Some(text);
+            } else {
+                self.shown_lines[line] = Some(text);
+            }
+            self.next_line = line + 1;
+            self.last_write = Some(Instant::now());
+            return;
+        }
+    }
+}
+
+/// Queues text and rumble patterns for the controllers and sends them no faster than
+/// [`CONTROLLER_WRITE_PERIOD`]. The queue is flushed by [`CommandScheduler::run`].
+///
+/// Only the latest text for each line is kept, and text that is already shown is not
+/// written again, so it is fine to update a line every loop.
+#[derive(Debug, Default)]
+pub struct ControllerWriter {
+    master: ControllerQueue,
+    partner: ControllerQueue,
+}
+
+impl ControllerWriter {
+    fn queue(&mut self, controller: Controller) -> &mut ControllerQueue {
+        match controller {
+            Controller::Master => &mut self.master,
+            Controller::Partner => &mut self.partner,
+        }
+    }
+
+    /// Queue text to be shown on a line of the controller screen.
+    /// The text is padded or truncated to fill the whole line.
+    pub fn set_line(&mut self, controller: Controller, line: u8, text: &str) {
+        let line = line as usize;
+        assert!(line < LINES, "Line number is too large for controller display ({line} >= {LINES})");
+
+        let text = format!("{text:<width$.width$}", width = ControllerLine::MAX_TEXT_LEN);
+        let queue = self.queue(controller);
+        if queue.shown_lines[line].as_ref() == Some(&text) {
+            queue.pending_lines[line] = None;
+        } else {
+            queue.pending_lines[line] = Some(text);
+        }
+    }
+
+    /// Queue a rumble pattern made of `.` (short), `-` (long) and ` ` (pause).
+    /// Patterns longer than 8 characters are truncated.
+    pub fn rumble(&mut self, controller: Controller, pattern: &str) {
+        let pattern = pattern.chars().take(8).collect();
+        self.queue(controller).rumbles.push_back(pattern);
+    }
+
+    /// Send at most one queued write to each controller.
+    pub fn flush(&mut self) {
+        self.master.flush(Controller::Master);
+        self.partner.flush(Controller::Partner);
+    }
+}
+
+/// Strips the module path from a type name, so that it fits on the controller screen.
+fn short_name(name: &str) -> &str {
+    let name = name.split('<').next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+/// Rumbles a controller with a pattern made of `.` (short), `-` (long) and ` ` (pause).
+pub struct RumbleCommand {
+    controller: Controller,
+    pattern: String,
+}
+
+impl RumbleCommand {
+    pub fn new(controller: Controller, pattern: impl Into<String>) -> Self {
+        Self {
+            controller,
+            pattern: pattern.into(),
+        }
+    }
+}
+
+impl Command for RumbleCommand {
+    fn get_requirements(&self) -> &[AnySubsystem] {
+        &[]
+    }
+
+    fn initialize(&mut self) -> Result {
+        let writer = CommandScheduler::controller_writer();
+        writer.borrow_mut().rumble(self.controller, &self.pattern);
+        Ok(())
+    }
+
+    fn is_finished(&self) -> Result<bool> {
+        Ok(true)
+    }
+
+    fn runs_when_disabled(&self) -> bool {
+        true
+    }
+}
+
+/// Shows up to three lines of status information on a controller screen, updated every loop.
+/// Runs until cancelled, including while the robot is disabled.
+pub struct StatusScreenCommand {
+    controller: Controller,
+    lines: Vec<Box<dyn FnMut() -> String>>,
+}
+
+impl StatusScreenCommand {
+    pub fn new(controller: Controller) -> Self {
+        Self {
+            controller,
+            lines: Vec::new(),
+        }
+    }
+
+    /// Add a line showing the text returned by `text`, such as the selected autonomous routine.
+    pub fn line(mut self, text: impl FnMut() -> String + 'static) -> Self {
+        assert!(self.lines.len() < LINES, "Controller screen only has {LINES} lines");
+        self.lines.push(Box::new(text));
+        self
+    }
+
+    /// Add a line showing the battery charge.
+    pub fn battery_line(self) -> Self {
+        self.line(|| match battery::capacity() {
+            Ok(capacity) => format!("Battery {capacity:.0}%"),
+            Err(_) => String::from("Battery ?"),
+        })
+    }
+
+    /// Add a line showing the command currently requiring the subsystem.
+    pub fn active_command_line(self, subsystem: AnySubsystem) -> Self {
+        self.line(move || {
+            let command = CommandScheduler::requiring(&subsystem)
+                .and_then(|command| {
+                    let command = command.try_borrow().ok()?;
+                    Some(String::from(short_name(command.name())))
+                })
+                .unwrap_or_else(|| String::from("-"));
+            match subsystem.try_borrow() {
+                Ok(subsystem) => format!("{}: {command}", short_name(subsystem.name())),
+                Err(_) => command,
+            }
+        })
+    }
+}
+
+impl Command for StatusScreenCommand {
+    fn get_requirements(&self) -> &[AnySubsystem] {
+        &[]
+    }
+
+    fn execute(&mut self) -> Result {
+        let writer = CommandScheduler::controller_writer();
+        let mut writer = writer.borrow_mut();
+        for (line, text) in self.lines.iter_mut().enumerate() {
+            writer.set_line(self.controller, line as u8, &text());
+        }
+        Ok(())
+    }
+
+    fn runs_when_disabled(&self) -> bool {
+        true
+    }
+}
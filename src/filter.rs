@@ -0,0 +1,89 @@
+use core::time::Duration;
+
+use pros::core::time::Instant;
+
+/// Which changes in the input a [`Debouncer`] should delay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DebounceType {
+    /// Only delay changes from false to true.
+    #[default]
+    Rising,
+    /// Only delay changes from true to false.
+    Falling,
+    /// Delay changes in both directions.
+    Both,
+}
+
+/// A filter that only lets a boolean signal change once it has held its new value
+/// for a given amount of time. Useful for noisy inputs like limit switches.
+#[derive(Debug, Clone)]
+pub struct Debouncer {
+    debounce_time: Duration,
+    debounce_type: DebounceType,
+    baseline: bool,
+    last_baseline_time: Instant,
+}
+
+impl Debouncer {
+    pub fn new(debounce_time: Duration, debounce_type: DebounceType) -> Self {
+        Self {
+            debounce_time,
+            debounce_type,
+            baseline: debounce_type == DebounceType::Falling,
+            last_baseline_time: Instant::now(),
+        }
+    }
+
+    /// Feed the current value of the input into the filter and return the debounced value.
+    pub fn calculate(&mut self, input: bool) -> bool {
+        let now = Instant::now();
+        if input == self.baseline {
+            self.last_baseline_time = now;
+        }
+
+        if now.saturating_duration_since(self.last_baseline_time) >= self.debounce_time {
+            if self.debounce_type == DebounceType::Both {
+                self.baseline = input;
+                self.last_baseline_time = now;
+            }
+            input
+        } else {
+            self.baseline
+        }
+    }
+}
+
+/// A filter that limits how quickly a value can change, in units per second.
+/// Useful for smoothing driver inputs to avoid tipping or wheel slip.
+#[derive(Debug, Clone)]
+pub struct SlewRateLimiter {
+    rate_limit: f32,
+    previous_value: f32,
+    previous_time: Instant,
+}
+
+impl SlewRateLimiter {
+    pub fn new(rate_limit: f32) -> Self {
+        Self {
+            rate_limit,
+            previous_value: 0.0,
+            previous_time: Instant::now(),
+        }
+    }
+
+    /// Feed the current value of the input into the filter and return the rate limited value.
+    pub fn calculate(&mut self, input: f32) -> f32 {
+        let now = Instant::now();
+        let elapsed = now.saturating_duration_since(self.previous_time).as_secs_f32();
+        let max_change = self.rate_limit * elapsed;
+        self.previous_value += (input - self.previous_value).clamp(-max_change, max_change);
+        self.previous_time = now;
+        self.previous_value
+    }
+
+    /// Reset the filter to the given value.
+    pub fn reset(&mut self, value: f32) {
+        self.previous_value = value;
+        self.previous_time = Instant::now();
+    }
+}
@@ -2,27 +2,33 @@
 
 extern crate alloc;
 
-use alloc::{rc::Rc, vec::Vec};
+use alloc::{rc::Rc, string::String, vec::Vec};
 use core::{
     cell::{Cell, RefCell},
+    fmt,
     hash::Hash,
     ops::Deref,
+    time::Duration,
 };
 
-use command::{Command, InterruptionBehavior};
+use command::Command;
 use event::EventLoop;
+use feedback::ControllerWriter;
 use hashbrown::{HashMap, HashSet};
-use pros::core::os_task_local;
+use pros::core::{os_task_local, time::Instant};
 use pros::devices::competition;
 use pros::devices::competition::CompetitionMode;
 use pros::prelude::*;
 use snafu::{OptionExt, Snafu};
-use subsystem::Subsystem;
+use subsystem::{Subsystem, SubsystemContext};
 use crate::SetDefaultCommandError::NotRegistered;
 
 pub mod command;
 pub mod event;
+pub mod feedback;
+pub mod filter;
 pub mod robot;
+pub mod shaping;
 pub mod subsystem;
 pub mod controller;
 
@@ -54,6 +60,16 @@ impl<T: Subsystem + 'static> From<T> for AnySubsystem {
     }
 }
 
+impl fmt::Display for AnySubsystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The subsystem may be mutably borrowed if it is currently in use.
+        match self.0.try_borrow() {
+            Ok(subsystem) => f.write_str(subsystem.name()),
+            Err(_) => f.write_str("<in use>"),
+        }
+    }
+}
+
 impl Deref for AnySubsystem {
     type Target = Rc<RefCell<dyn Subsystem>>;
 
@@ -78,6 +94,25 @@ impl Hash for AnyCommand {
     }
 }
 
+impl fmt::Debug for AnyCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The command may be mutably borrowed if it is currently running.
+        match self.0.try_borrow() {
+            Ok(command) => f.debug_tuple("AnyCommand").field(&command.name()).finish(),
+            Err(_) => f.debug_tuple("AnyCommand").field(&"<running>").finish(),
+        }
+    }
+}
+
+impl fmt::Display for AnyCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.try_borrow() {
+            Ok(command) => f.write_str(command.name()),
+            Err(_) => f.write_str("<running>"),
+        }
+    }
+}
+
 impl From<Rc<RefCell<dyn Command>>> for AnyCommand {
     fn from(command: Rc<RefCell<dyn Command>>) -> Self {
         Self(command)
@@ -106,6 +141,56 @@ pub enum SetDefaultCommandError {
     NotRegistered,
 }
 
+/// The result of asking the scheduler to schedule a command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleOutcome {
+    /// The command was initialized and is now running.
+    Started,
+    /// The command was scheduled from inside the scheduler's run loop and
+    /// will be scheduled once the current run has finished.
+    Queued,
+    /// The command was already running.
+    AlreadyScheduled,
+    /// The command was refused because the given command requires one of the same
+    /// subsystems and cannot be interrupted by it.
+    RejectedBy(AnyCommand),
+    /// The command was refused because the scheduler is disabled.
+    SchedulerDisabled,
+    /// The command was refused because it requires the given locked subsystem.
+    SubsystemLocked(AnySubsystem),
+}
+
+impl ScheduleOutcome {
+    /// Returns true if the command is running or will be run.
+    pub fn is_accepted(&self) -> bool {
+        matches!(self, Self::Started | Self::Queued | Self::AlreadyScheduled)
+    }
+}
+
+/// The subsystems required by a command, split by access type.
+struct Requirements {
+    exclusive: HashSet<AnySubsystem>,
+    shared: HashSet<AnySubsystem>,
+}
+
+impl Requirements {
+    fn of(command: &dyn Command) -> Self {
+        let exclusive: HashSet<_> = command.get_requirements().iter().cloned().collect();
+        // An exclusive requirement supersedes a shared one on the same subsystem.
+        let shared = command
+            .get_shared_requirements()
+            .iter()
+            .filter(|r| !exclusive.contains(*r))
+            .cloned()
+            .collect();
+        Self { exclusive, shared }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &AnySubsystem> {
+        self.exclusive.iter().chain(self.shared.iter())
+    }
+}
+
 #[derive(Default)]
 struct CommandSchedulerState {
     subsystems: RefCell<HashMap<AnySubsystem, Option<AnyCommand>>>,
@@ -114,8 +199,17 @@ struct CommandSchedulerState {
     to_cancel: RefCell<Vec<AnyCommand>>,
     scheduled_commands: RefCell<HashSet<AnyCommand>>,
     requirements: RefCell<HashMap<AnySubsystem, AnyCommand>>,
+    shared_requirements: RefCell<HashMap<AnySubsystem, HashSet<AnyCommand>>>,
     button_loop: Rc<RefCell<EventLoop>>,
+    /// Event loops registered by name, polled after the button loop in registration order.
+    event_loops: RefCell<Vec<(String, Rc<RefCell<EventLoop>>)>>,
+    controller_writer: Rc<RefCell<ControllerWriter>>,
     ending_commands: RefCell<HashSet<AnyCommand>>,
+    last_run: Cell<Option<Instant>>,
+    /// The number of times the scheduler has been run.
+    run_count: Cell<u64>,
+    disabled: Cell<bool>,
+    locked_subsystems: RefCell<HashSet<AnySubsystem>>,
 }
 
 impl CommandSchedulerState {
@@ -128,14 +222,48 @@ impl CommandSchedulerState {
         self.requirements.borrow().get(subsystem).cloned()
     }
 
-    fn init_command(
-        &self,
-        command: AnyCommand,
-        requirements: HashSet<AnySubsystem>,
-    ) -> Result {
-        self.requirements
-            .borrow_mut()
-            .extend(requirements.into_iter().map(|r| (r, command.clone())));
+    fn sharing(&self, subsystem: &AnySubsystem) -> Vec<AnyCommand> {
+        self.shared_requirements
+            .borrow()
+            .get(subsystem)
+            .map(|commands| commands.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns true if a command requires exclusive access to the subsystem.
+    /// Commands that only share the subsystem do not keep its default command from running.
+    fn is_required(&self, subsystem: &AnySubsystem) -> bool {
+        self.requirements.borrow().contains_key(subsystem)
+    }
+
+    /// Returns the reason the scheduler would refuse a command with the given requirements,
+    /// or `None` if it would currently accept it.
+    fn refusal(&self, requirements: &Requirements) -> Option<ScheduleOutcome> {
+        if self.disabled.get() {
+            return Some(ScheduleOutcome::SchedulerDisabled);
+        }
+        let locked_subsystems = self.locked_subsystems.borrow();
+        requirements
+            .iter()
+            .find(|r| locked_subsystems.contains(*r))
+            .map(|subsystem| ScheduleOutcome::SubsystemLocked(subsystem.clone()))
+    }
+
+    fn init_command(&self, command: AnyCommand, requirements: Requirements) -> Result {
+        self.requirements.borrow_mut().extend(
+            requirements
+                .exclusive
+                .into_iter()
+                .map(|r| (r, command.clone())),
+        );
+        let mut shared_requirements = self.shared_requirements.borrow_mut();
+        for requirement in requirements.shared {
+            shared_requirements
+                .entry(requirement)
+                .or_default()
+                .insert(command.clone());
+        }
+        drop(shared_requirements);
 
         let mut scheduled_commands = self.scheduled_commands.borrow_mut();
         let command = scheduled_commands.entry(command).insert();
@@ -158,42 +286,86 @@ impl CommandSchedulerState {
         }
 
         self.ending_commands.borrow_mut().insert(command.clone());
-        {
-            let mut command = (*command.0).borrow_mut();
-            command.end(true)?;
-        }
+        let res = (*command.0).borrow_mut().end(true);
         self.ending_commands.borrow_mut().remove(command);
         self.scheduled_commands.borrow_mut().remove(command);
-        {
-            let requirements = CommandScheduler::requirements_of(&*(*command.0).borrow());
-            for requirement in requirements {
-                self.requirements.borrow_mut().remove(&requirement);
+        self.release_requirements(command, &*(*command.0).borrow());
+
+        res
+    }
+
+    /// Removes the command from the requirements of every subsystem it requires.
+    fn release_requirements(&self, handle: &AnyCommand, command: &dyn Command) {
+        let requirements = Requirements::of(command);
+        for requirement in &requirements.exclusive {
+            self.requirements.borrow_mut().remove(requirement);
+        }
+        let mut shared_requirements = self.shared_requirements.borrow_mut();
+        for requirement in &requirements.shared {
+            if let Some(commands) = shared_requirements.get_mut(requirement) {
+                commands.remove(handle);
+                if commands.is_empty() {
+                    shared_requirements.remove(requirement);
+                }
             }
         }
+    }
+
+    /// The commands that must be interrupted for a command with the given requirements to run.
+    /// Only exclusive requirements conflict, with the command that currently owns the subsystem.
+    /// Shared requirements run alongside the owner and any other readers.
+    fn conflicting(&self, requirements: &Requirements) -> HashSet<AnyCommand> {
+        requirements
+            .exclusive
+            .iter()
+            .filter_map(|requirement| self.requiring(requirement))
+            .collect()
+    }
+
+    /// Runs one iteration of a scheduled command, ending it if it has finished.
+    fn run_command(&self, command: &AnyCommand, comp_mode: CompetitionMode) -> Result {
+        let mut command_ref = (*command.0).borrow_mut();
+        if comp_mode == CompetitionMode::Disabled && !command_ref.runs_when_disabled() {
+            drop(command_ref);
+            return self.cancel(command);
+        }
 
+        command_ref.execute()?;
+        if command_ref.is_finished()? {
+            self.ending_commands.borrow_mut().insert(command.clone());
+            let res = command_ref.end(false);
+            self.ending_commands.borrow_mut().remove(command);
+            self.scheduled_commands.borrow_mut().remove(command);
+            self.release_requirements(command, &*command_ref);
+            res?;
+        }
         Ok(())
     }
 
-    fn schedule_now(&self, command: AnyCommand) -> Result {
+    /// Schedules the command immediately, interrupting any commands requiring the same
+    /// subsystems if they allow it.
+    fn schedule_now(&self, command: AnyCommand) -> Result<ScheduleOutcome> {
         if self.is_scheduled(&command) {
-            return Ok(());
+            return Ok(ScheduleOutcome::AlreadyScheduled);
         }
 
-        let requirements = CommandScheduler::requirements_of(&*(*command.0).borrow());
+        let requirements = Requirements::of(&*(*command.0).borrow());
+        if let Some(refusal) = self.refusal(&requirements) {
+            return Ok(refusal);
+        }
 
-        if requirements.is_disjoint(&self.requirements.borrow().keys().cloned().collect()) {
-            self.init_command(command, requirements)
+        let requiring_commands = self.conflicting(&requirements);
+        if requiring_commands.is_empty() {
+            self.init_command(command, requirements)?;
         } else {
-            let requiring_commands = requirements
-                .iter()
-                .filter_map(|r| self.requiring(r))
-                .collect::<Vec<_>>();
-
+            let incoming = (*command.0).borrow().get_interruption_behavior();
             for requiring in &requiring_commands {
-                if (*requiring.0).borrow().get_interruption_behavior()
-                    == InterruptionBehavior::CancelIncoming
+                if !(*requiring.0)
+                    .borrow()
+                    .get_interruption_behavior()
+                    .can_be_interrupted_by(incoming)
                 {
-                    return Ok(());
+                    return Ok(ScheduleOutcome::RejectedBy(requiring.clone()));
                 }
             }
 
@@ -201,7 +373,18 @@ impl CommandSchedulerState {
                 self.cancel(requiring)?;
             }
 
-            self.init_command(command, requirements)
+            self.init_command(command, requirements)?;
+        }
+
+        Ok(ScheduleOutcome::Started)
+    }
+}
+
+/// Stores `new` in `result` if it is an error and `result` does not already hold one.
+fn keep_first_error(result: &mut Result, new: Result) {
+    if let Err(err) = new {
+        if result.is_ok() {
+            *result = Err(err);
         }
     }
 }
@@ -230,12 +413,23 @@ impl CommandScheduler {
     }
 
     /// Schedule a command to run.
-    pub fn schedule(command: Rc<RefCell<dyn Command>>) -> Result {
+    ///
+    /// Commands scheduled from inside the scheduler's run loop are [queued](ScheduleOutcome::Queued)
+    /// and may still be rejected once the run has finished.
+    /// Refused commands are reported through the returned [`ScheduleOutcome`], not as errors.
+    pub fn schedule(command: Rc<RefCell<dyn Command>>) -> Result<ScheduleOutcome> {
         STATE.with(|state| {
             let command = AnyCommand(command);
             if state.in_run_loop.get() {
+                if state.is_scheduled(&command) {
+                    return Ok(ScheduleOutcome::AlreadyScheduled);
+                }
+                let requirements = Requirements::of(&*(*command.0).borrow());
+                if let Some(refusal) = state.refusal(&requirements) {
+                    return Ok(refusal);
+                }
                 state.to_schedule.borrow_mut().push(command);
-                return Ok(());
+                return Ok(ScheduleOutcome::Queued);
             }
 
             state.schedule_now(command)
@@ -251,8 +445,8 @@ impl CommandScheduler {
         command: AnyCommand,
     ) -> core::result::Result<(), SetDefaultCommandError> {
         STATE.with(|state| {
-            let requirements = CommandScheduler::requirements_of(&*command.borrow());
-            if !requirements.contains(subsystem) {
+            let requirements = Requirements::of(&*command.borrow());
+            if !requirements.exclusive.contains(subsystem) {
                 return MustRequireSubsystemSnafu.fail();
             }
 
@@ -287,17 +481,48 @@ impl CommandScheduler {
 
     pub fn run() -> Result {
         STATE.with(|state| {
-            for subsystem_ctx in state.subsystems.borrow().keys() {
-                let mut subsystem = (*subsystem_ctx.0).borrow_mut();
-                subsystem.periodic(subsystem_ctx.clone());
+            state.run_count.set(state.run_count.get() + 1);
+            let now = Instant::now();
+            let loop_time = state
+                .last_run
+                .replace(Some(now))
+                .map_or(Duration::ZERO, |last| now.saturating_duration_since(last));
+
+            for (subsystem, default_command) in state.subsystems.borrow().iter() {
+                let ctx = SubsystemContext {
+                    subsystem: subsystem.clone(),
+                    current_command: state.requiring(subsystem),
+                    shared_commands: state.sharing(subsystem),
+                    default_command: default_command.clone(),
+                    loop_time,
+                };
+                let mut subsystem = (*subsystem.0).borrow_mut();
+                subsystem.periodic(&ctx);
                 if robot::is_sim() {
-                    subsystem.sim_periodic(subsystem_ctx.clone());
+                    subsystem.sim_periodic(&ctx);
                 }
             }
 
-            let button_loop = state.button_loop.clone();
-            (*button_loop).borrow_mut().poll()?;
+            // Writes queued by commands during the previous run are sent here,
+            // so the controller is written to even while the scheduler is disabled.
+            state.controller_writer.borrow_mut().flush();
+
+            if state.disabled.get() {
+                return Ok(());
+            }
+
+            let event_loops = core::iter::once(state.button_loop.clone())
+                .chain(state.event_loops.borrow().iter().map(|(_, event_loop)| event_loop.clone()))
+                .collect::<Vec<_>>();
+            // Errors are collected so that one failing binding or command does not stop
+            // the rest of the scheduler from running. The first one is returned at the end.
+            let mut result = Ok(());
+            for event_loop in event_loops {
+                keep_first_error(&mut result, (*event_loop).borrow_mut().poll());
+            }
 
+            // Nothing returns early while in the run loop, so the flag is always reset and
+            // queued schedules and cancels are always drained below.
             state.in_run_loop.set(true);
             let comp_mode = competition::mode();
 
@@ -309,52 +534,42 @@ impl CommandScheduler {
                 .collect::<Vec<_>>();
 
             for command in scheduled_commands {
-                let mut command_ref = (*command.0).borrow_mut();
-                if comp_mode == CompetitionMode::Disabled && !command_ref.runs_when_disabled() {
-                    state.cancel(&command)?;
-                }
-
-                command_ref.execute()?;
-                if command_ref.is_finished()? {
-                    state.ending_commands.borrow_mut().insert(command.clone());
-                    let res = command_ref.end(false);
-                    state.ending_commands.borrow_mut().remove(&command);
-                    res?;
-                    state.scheduled_commands.borrow_mut().remove(&command);
-                    let requirements = command_ref.get_requirements();
-                    for requirement in requirements {
-                        state.requirements.borrow_mut().remove(requirement);
-                    }
-                }
+                keep_first_error(&mut result, state.run_command(&command, comp_mode));
             }
 
             state.in_run_loop.set(false);
 
             let to_schedule = state.to_schedule.take();
             for command in to_schedule {
-                state.schedule_now(command)?;
+                keep_first_error(&mut result, state.schedule_now(command).map(|_| ()));
             }
 
             let to_cancel = state.to_cancel.take();
             for command in to_cancel {
-                state.cancel(&command)?;
+                keep_first_error(&mut result, state.cancel(&command));
             }
 
             // Add default commands for un-required registered subsystems.
             for (subsystem, command) in state.subsystems.borrow().iter() {
                 if let Some(default_command) = command {
-                    if !state.requirements.borrow().contains_key(subsystem) {
-                        state.schedule_now(default_command.clone())?;
+                    if !state.is_required(subsystem)
+                        && !state.locked_subsystems.borrow().contains(subsystem)
+                    {
+                        keep_first_error(
+                            &mut result,
+                            state.schedule_now(default_command.clone()).map(|_| ()),
+                        );
                     }
                 }
             }
 
-            Ok(())
+            result
         })
     }
 
-    fn requirements_of(command: &dyn Command) -> HashSet<AnySubsystem> {
-        command.get_requirements().iter().cloned().collect()
+    /// The number of times [`CommandScheduler::run`] has been called.
+    pub(crate) fn run_count() -> u64 {
+        STATE.with(|state| state.run_count.get())
     }
 
     pub fn cancel_all() -> Result {
@@ -366,18 +581,112 @@ impl CommandScheduler {
                 .cloned()
                 .collect::<Vec<_>>();
 
+            let mut result = Ok(());
             for command in scheduled_commands {
-                state.cancel(&command)?;
+                keep_first_error(&mut result, state.cancel(&command));
             }
+            result
+        })
+    }
 
-            Ok(())
+    /// Cancel every scheduled command that does not [run when disabled](Command::runs_when_disabled).
+    pub fn cancel_disabled_commands() -> Result {
+        STATE.with(|state| {
+            let scheduled_commands = state
+                .scheduled_commands
+                .borrow()
+                .iter()
+                .filter(|command| !(*command.0).borrow().runs_when_disabled())
+                .cloned()
+                .collect::<Vec<_>>();
+
+            let mut result = Ok(());
+            for command in scheduled_commands {
+                keep_first_error(&mut result, state.cancel(&command));
+            }
+            result
+        })
+    }
+
+    /// Pause command execution. Subsystem periodic methods continue to run,
+    /// but scheduled commands are not executed, button bindings are not polled
+    /// and new commands are refused until [`CommandScheduler::enable`] is called.
+    pub fn disable() {
+        STATE.with(|state| state.disabled.set(true));
+    }
+
+    /// Resume command execution after a call to [`CommandScheduler::disable`].
+    pub fn enable() {
+        STATE.with(|state| state.disabled.set(false));
+    }
+
+    pub fn is_enabled() -> bool {
+        STATE.with(|state| !state.disabled.get())
+    }
+
+    /// Lock a subsystem, cancelling any commands currently requiring it.
+    /// Commands that require a locked subsystem are refused with
+    /// [`ScheduleOutcome::SubsystemLocked`] until the subsystem is unlocked.
+    pub fn lock_subsystem(subsystem: &AnySubsystem) -> Result {
+        STATE.with(|state| {
+            state.locked_subsystems.borrow_mut().insert(subsystem.clone());
+            let mut result = Ok(());
+            for command in state.requiring(subsystem).into_iter().chain(state.sharing(subsystem)) {
+                keep_first_error(&mut result, state.cancel(&command));
+            }
+            result
         })
     }
 
+    pub fn unlock_subsystem(subsystem: &AnySubsystem) {
+        STATE.with(|state| {
+            state.locked_subsystems.borrow_mut().remove(subsystem);
+        });
+    }
+
+    pub fn is_locked(subsystem: &AnySubsystem) -> bool {
+        STATE.with(|state| state.locked_subsystems.borrow().contains(subsystem))
+    }
+
     pub fn button_event_loop() -> Rc<RefCell<EventLoop>> {
         STATE.with(|state| state.button_loop.clone())
     }
 
+    /// Get the event loop registered with the given name, creating and registering it if needed.
+    ///
+    /// Registered event loops are polled on every scheduler run after the button event loop,
+    /// in the order they were registered. Use [`EventLoop::disable`] to stop polling one.
+    pub fn event_loop(name: &str) -> Rc<RefCell<EventLoop>> {
+        STATE.with(|state| {
+            let mut event_loops = state.event_loops.borrow_mut();
+            if let Some((_, event_loop)) = event_loops.iter().find(|(n, _)| n == name) {
+                return event_loop.clone();
+            }
+            let event_loop = Rc::new(RefCell::new(EventLoop::default()));
+            event_loops.push((String::from(name), event_loop.clone()));
+            event_loop
+        })
+    }
+
+    /// Unregister the event loop with the given name, returning it if it was registered.
+    pub fn remove_event_loop(name: &str) -> Option<Rc<RefCell<EventLoop>>> {
+        STATE.with(|state| {
+            let mut event_loops = state.event_loops.borrow_mut();
+            let index = event_loops.iter().position(|(n, _)| n == name)?;
+            Some(event_loops.remove(index).1)
+        })
+    }
+
+    /// The queue of controller screen and rumble writes, flushed once per scheduler run.
+    pub fn controller_writer() -> Rc<RefCell<ControllerWriter>> {
+        STATE.with(|state| state.controller_writer.clone())
+    }
+
+    /// The command currently requiring exclusive access to the subsystem, if any.
+    pub fn requiring(subsystem: &AnySubsystem) -> Option<AnyCommand> {
+        STATE.with(|state| state.requiring(subsystem))
+    }
+
     pub fn is_scheduled(command: &Rc<RefCell<dyn Command>>) -> bool {
         STATE.with(|state| state.is_scheduled(&AnyCommand(command.clone())))
     }
@@ -1,9 +1,14 @@
-use core::time::Duration;
-use pros::core::task::Interval;
+use alloc::collections::VecDeque;
+use core::{cell::Cell, error::Error, time::Duration};
+use pros::core::{os_task_local, time::Instant};
 use pros::devices::competition;
 use pros::devices::competition::CompetitionMode;
 use pros::prelude::*;
 
+use snafu::Snafu;
+
+use crate::{controller::Trigger, CommandScheduler};
+
 /// Returns true if the code is running on a real robot and not in simulation.
 pub const fn is_real() -> bool {
     cfg!(target_os = "vexos")
@@ -14,7 +19,207 @@ pub const fn is_sim() -> bool {
     !is_real()
 }
 
+/// The mode the robot is running in, as seen by [`start_robot`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RobotMode {
+    Disabled,
+    Autonomous,
+    Opcontrol,
+    /// Replaces [`RobotMode::Opcontrol`] when [`ScheduledRobot::test_mode`] returns true.
+    Test,
+}
+
+/// When [`start_robot`] runs the [`CommandScheduler`] relative to [`ScheduledRobot::periodic`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SchedulerRun {
+    /// Run the scheduler before the robot's periodic methods.
+    BeforePeriodic,
+    /// Run the scheduler after the robot's periodic methods.
+    #[default]
+    AfterPeriodic,
+    /// Never run the scheduler. It must be run manually with [`CommandScheduler::run`].
+    Manual,
+}
+
+/// What [`start_robot`] does when an iteration takes longer than [`RobotConfig::period`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverrunPolicy {
+    /// Drop the missed iterations and wait for the next period boundary.
+    Skip,
+    /// Run a missed iteration immediately to get back on schedule. Missed iterations beyond
+    /// the most recent period are dropped.
+    #[default]
+    CatchUp,
+    /// Log the overrun and start the next period from now.
+    Warn,
+}
+
+/// The part of the robot loop that returned an error in [`start_robot`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RobotCallback {
+    /// The init method of the current mode, e.g. [`ScheduledRobot::autonomous_init`].
+    ModeInit,
+    /// The periodic method of the current mode, e.g. [`ScheduledRobot::autonomous_periodic`].
+    ModePeriodic,
+    /// The exit method of the mode being left, e.g. [`ScheduledRobot::autonomous_exit`].
+    ModeExit,
+    /// [`ScheduledRobot::periodic`].
+    Periodic,
+    /// [`ScheduledRobot::sim_periodic`].
+    SimPeriodic,
+    /// The [`CommandScheduler`].
+    Scheduler,
+}
+
+/// The most errors [`start_robot`] tolerates within a time window before safing the robot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorRate {
+    pub max_errors: usize,
+    pub window: Duration,
+}
+
+impl ErrorRate {
+    pub const fn new(max_errors: usize, window: Duration) -> Self {
+        Self { max_errors, window }
+    }
+}
+
+/// Timing and error handling configuration for [`start_robot`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RobotConfig {
+    /// Time between the start of each loop iteration.
+    pub period: Duration,
+    /// What to do when an iteration overruns the period.
+    pub overrun_policy: OverrunPolicy,
+    /// Safe the robot once errors exceed this rate. `None` keeps looping regardless of errors.
+    pub max_error_rate: Option<ErrorRate>,
+    /// The kind of match the robot is running, used to time periods with [`MatchTimer`].
+    pub match_type: MatchType,
+}
+
+impl RobotConfig {
+    pub const fn new(period: Duration, overrun_policy: OverrunPolicy) -> Self {
+        Self {
+            period,
+            overrun_policy,
+            max_error_rate: None,
+            match_type: MatchType::HeadToHead,
+        }
+    }
+
+    pub const fn with_match_type(mut self, match_type: MatchType) -> Self {
+        self.match_type = match_type;
+        self
+    }
+
+    pub const fn with_max_error_rate(mut self, max_error_rate: ErrorRate) -> Self {
+        self.max_error_rate = Some(max_error_rate);
+        self
+    }
+}
+
+impl Default for RobotConfig {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(20), OverrunPolicy::default())
+    }
+}
+
+#[derive(Debug, Snafu)]
+pub enum StartRobotError {
+    /// The robot was safed because its callbacks returned errors too often.
+    #[snafu(display(
+        "Robot safed after more than {} errors in {:?}",
+        rate.max_errors,
+        rate.window
+    ))]
+    ErrorRateExceeded { rate: ErrorRate },
+}
+
+/// Measured timing of the [`start_robot`] loop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LoopStats {
+    iterations: u64,
+    overruns: u64,
+    last_period: Duration,
+    min_period: Duration,
+    max_period: Duration,
+    total_period: Duration,
+}
+
+impl LoopStats {
+    fn record_period(&mut self, period: Duration) {
+        if self.iterations == 0 || period < self.min_period {
+            self.min_period = period;
+        }
+        self.max_period = self.max_period.max(period);
+        self.last_period = period;
+        self.total_period += period;
+        self.iterations += 1;
+    }
+
+    /// Number of periods measured.
+    pub fn iterations(&self) -> u64 {
+        self.iterations
+    }
+    /// Number of iterations that took longer than the configured period.
+    pub fn overruns(&self) -> u64 {
+        self.overruns
+    }
+    /// Time between the start of the last two iterations.
+    pub fn last_period(&self) -> Duration {
+        self.last_period
+    }
+    pub fn min_period(&self) -> Duration {
+        self.min_period
+    }
+    pub fn max_period(&self) -> Duration {
+        self.max_period
+    }
+    pub fn average_period(&self) -> Duration {
+        match u32::try_from(self.iterations) {
+            Ok(0) => Duration::ZERO,
+            Ok(iterations) => self.total_period / iterations,
+            Err(_) => Duration::from_secs_f64(
+                self.total_period.as_secs_f64() / self.iterations as f64,
+            ),
+        }
+    }
+}
+
+os_task_local! {
+    static LOOP_STATS: Cell<LoopStats> = Cell::new(LoopStats::default());
+}
+
+/// Timing statistics of the [`start_robot`] loop running on the current task.
+pub fn loop_stats() -> LoopStats {
+    LOOP_STATS.get()
+}
+
 pub trait ScheduledRobot {
+    /// Timing configuration of the robot loop. Read once when [`start_robot`] is called.
+    fn config(&self) -> RobotConfig {
+        RobotConfig::default()
+    }
+
+    /// Whether driver control should run test mode instead of opcontrol.
+    /// Defaults to true if the `test-mode` feature is enabled.
+    fn test_mode(&self) -> bool {
+        cfg!(feature = "test-mode")
+    }
+
+    /// When to run the command scheduler in each loop.
+    fn scheduler_run(&self) -> SchedulerRun {
+        SchedulerRun::default()
+    }
+
+    /// Called by [`start_robot`] after logging an error returned during the loop.
+    fn on_error(&mut self, _mode: RobotMode, _callback: RobotCallback, _error: &dyn Error) {}
+
+    /// Called every loop in every mode.
+    ///
+    /// [`start_robot`] already runs the [`CommandScheduler`] as configured by
+    /// [`ScheduledRobot::scheduler_run`]. Do not also call [`CommandScheduler::run`] here
+    /// unless that returns [`SchedulerRun::Manual`], or commands will run twice per loop.
     fn periodic(&mut self) -> Result {
         Ok(())
     }
@@ -27,55 +232,368 @@ pub trait ScheduledRobot {
     fn disabled_periodic(&mut self) -> Result {
         Ok(())
     }
+    fn disabled_exit(&mut self) -> Result {
+        Ok(())
+    }
     fn autonomous_init(&mut self) -> Result {
         Ok(())
     }
     fn autonomous_periodic(&mut self) -> Result {
         Ok(())
     }
+    fn autonomous_exit(&mut self) -> Result {
+        Ok(())
+    }
     fn opcontrol_init(&mut self) -> Result {
         Ok(())
     }
     fn opcontrol_periodic(&mut self) -> Result {
         Ok(())
     }
+    fn opcontrol_exit(&mut self) -> Result {
+        Ok(())
+    }
+    fn test_init(&mut self) -> Result {
+        Ok(())
+    }
+    fn test_periodic(&mut self) -> Result {
+        Ok(())
+    }
+    fn test_exit(&mut self) -> Result {
+        Ok(())
+    }
 }
 
-pub const ITERATION_PERIOD: Duration = Duration::from_millis(20);
+/// Length of the autonomous period of a head-to-head match.
+pub const AUTONOMOUS_PERIOD: Duration = Duration::from_secs(15);
+/// Length of the driver control period of a head-to-head match.
+pub const OPCONTROL_PERIOD: Duration = Duration::from_secs(105);
+/// Length of a programming or driver skills run.
+pub const SKILLS_PERIOD: Duration = Duration::from_secs(60);
+
+/// The kind of match being played, which determines the length of each period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MatchType {
+    /// A 15 second autonomous period followed by 1:45 of driver control.
+    #[default]
+    HeadToHead,
+    /// 60 second programming and driver skills runs.
+    Skills,
+}
 
+impl MatchType {
+    /// Length of the period the robot runs in `mode`, or `None` if the mode is not timed.
+    pub const fn period_length(&self, mode: RobotMode) -> Option<Duration> {
+        match (self, mode) {
+            (MatchType::HeadToHead, RobotMode::Autonomous) => Some(AUTONOMOUS_PERIOD),
+            (MatchType::HeadToHead, RobotMode::Opcontrol) => Some(OPCONTROL_PERIOD),
+            (MatchType::Skills, RobotMode::Autonomous | RobotMode::Opcontrol) => {
+                Some(SKILLS_PERIOD)
+            }
+            (_, RobotMode::Disabled | RobotMode::Test) => None,
+        }
+    }
+}
+
+struct MatchTimerState {
+    match_type: Cell<MatchType>,
+    period: Cell<Option<(RobotMode, Instant)>>,
+}
+
+os_task_local! {
+    static MATCH_TIMER: MatchTimerState = MatchTimerState {
+        match_type: Cell::new(MatchType::HeadToHead),
+        period: Cell::new(None),
+    };
+}
+
+/// Tracks time in the current autonomous or driver control period.
+///
+/// [`start_robot`] starts the timer when entering autonomous or opcontrol and stops it
+/// in every other mode.
+pub struct MatchTimer;
+
+impl MatchTimer {
+    pub fn match_type() -> MatchType {
+        MATCH_TIMER.with(|timer| timer.match_type.get())
+    }
+
+    pub fn set_match_type(match_type: MatchType) {
+        MATCH_TIMER.with(|timer| timer.match_type.set(match_type));
+    }
+
+    /// Start timing the period of `mode` from now. Stops the timer if `mode` is not timed.
+    pub fn start(mode: RobotMode) {
+        let period = Self::match_type()
+            .period_length(mode)
+            .map(|_| (mode, Instant::now()));
+        MATCH_TIMER.with(|timer| timer.period.set(period));
+    }
+
+    pub fn stop() {
+        MATCH_TIMER.with(|timer| timer.period.set(None));
+    }
+
+    /// The mode of the period being timed, if any.
+    pub fn mode() -> Option<RobotMode> {
+        MATCH_TIMER.with(|timer| timer.period.get()).map(|(mode, _)| mode)
+    }
+
+    /// Time since the current period started.
+    pub fn elapsed() -> Option<Duration> {
+        MATCH_TIMER
+            .with(|timer| timer.period.get())
+            .map(|(_, started)| started.elapsed())
+    }
+
+    /// Time left in the current period, saturating at zero once it has run out.
+    pub fn remaining() -> Option<Duration> {
+        let (mode, started) = MATCH_TIMER.with(|timer| timer.period.get())?;
+        let length = Self::match_type().period_length(mode)?;
+        Some(length.saturating_sub(started.elapsed()))
+    }
+
+    /// Whether less than `remaining` is left in the current period.
+    pub fn is_below(remaining: Duration) -> bool {
+        Self::remaining().is_some_and(|left| left < remaining)
+    }
+}
+
+/// Triggers for the competition mode of the robot, for binding commands to mode transitions.
+pub struct RobotModeTriggers;
+
+impl RobotModeTriggers {
+    /// Active while the robot is in autonomous mode.
+    pub fn autonomous() -> Trigger {
+        Trigger::new(|| competition::mode() == CompetitionMode::Autonomous)
+    }
+
+    /// Active while the robot is in driver control mode.
+    pub fn opcontrol() -> Trigger {
+        Trigger::new(|| competition::mode() == CompetitionMode::Opcontrol)
+    }
+
+    /// Active while the robot is disabled.
+    pub fn disabled() -> Trigger {
+        Trigger::new(|| competition::mode() == CompetitionMode::Disabled)
+    }
+
+    /// Active while less than `remaining` is left in the current autonomous or
+    /// driver control period, as timed by [`MatchTimer`].
+    pub fn match_time_below(remaining: Duration) -> Trigger {
+        Trigger::new(move || MatchTimer::is_below(remaining))
+    }
+}
+
+fn current_mode(robot: &impl ScheduledRobot) -> RobotMode {
+    match competition::mode() {
+        CompetitionMode::Disabled => RobotMode::Disabled,
+        CompetitionMode::Autonomous => RobotMode::Autonomous,
+        CompetitionMode::Opcontrol if robot.test_mode() => RobotMode::Test,
+        CompetitionMode::Opcontrol => RobotMode::Opcontrol,
+    }
+}
+
+fn mode_init(robot: &mut impl ScheduledRobot, mode: RobotMode) -> Result {
+    match mode {
+        RobotMode::Disabled => robot.disabled_init(),
+        RobotMode::Autonomous => robot.autonomous_init(),
+        RobotMode::Opcontrol => robot.opcontrol_init(),
+        RobotMode::Test => robot.test_init(),
+    }
+}
+
+fn mode_periodic(robot: &mut impl ScheduledRobot, mode: RobotMode) -> Result {
+    match mode {
+        RobotMode::Disabled => robot.disabled_periodic(),
+        RobotMode::Autonomous => robot.autonomous_periodic(),
+        RobotMode::Opcontrol => robot.opcontrol_periodic(),
+        RobotMode::Test => robot.test_periodic(),
+    }
+}
+
+fn mode_exit(robot: &mut impl ScheduledRobot, mode: RobotMode) -> Result {
+    match mode {
+        RobotMode::Disabled => robot.disabled_exit(),
+        RobotMode::Autonomous => robot.autonomous_exit(),
+        RobotMode::Opcontrol => robot.opcontrol_exit(),
+        RobotMode::Test => robot.test_exit(),
+    }
+}
+
+/// Sleeps until the start of the next iteration and returns when the one after it is due.
+fn wait_for_next_iteration(config: &RobotConfig, deadline: Instant) -> Instant {
+    let now = Instant::now();
+    if now < deadline {
+        delay(deadline - now);
+        return deadline + config.period;
+    }
+
+    LOOP_STATS.with(|stats| {
+        let mut updated = stats.get();
+        updated.overruns += 1;
+        stats.set(updated);
+    });
+    match config.overrun_policy {
+        OverrunPolicy::Skip => {
+            let mut next = deadline;
+            while next <= now {
+                next += config.period;
+            }
+            delay(next - now);
+            next + config.period
+        }
+        OverrunPolicy::CatchUp => {
+            // Yield to other tasks even though the next iteration is already due.
+            delay(Duration::ZERO);
+            // Never fall more than one period behind, so a long overrun causes at most one
+            // extra iteration instead of a burst of them.
+            (deadline + config.period).max(now)
+        }
+        OverrunPolicy::Warn => {
+            println!(
+                "Robot loop overran its {:?} period by {:?}",
+                config.period,
+                now - deadline
+            );
+            now + config.period
+        }
+    }
+}
+
+/// Logs and reports errors returned from the robot loop, and tracks how often they happen.
+struct ErrorHandler {
+    max_error_rate: Option<ErrorRate>,
+    errors: VecDeque<Instant>,
+}
+
+impl ErrorHandler {
+    fn new(config: &RobotConfig) -> Self {
+        Self {
+            max_error_rate: config.max_error_rate,
+            errors: VecDeque::new(),
+        }
+    }
+
+    /// Handles the result of a callback, returning an error if the robot needs to be safed.
+    fn handle(
+        &mut self,
+        robot: &mut impl ScheduledRobot,
+        mode: RobotMode,
+        callback: RobotCallback,
+        result: Result,
+    ) -> Result {
+        let Err(error) = result else {
+            return Ok(());
+        };
+        println!("Error in {:?} {:?}: {}", mode, callback, error);
+        robot.on_error(mode, callback, error.as_ref());
+
+        let Some(rate) = self.max_error_rate else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        self.errors.push_back(now);
+        while self
+            .errors
+            .front()
+            .is_some_and(|time| now - *time > rate.window)
+        {
+            self.errors.pop_front();
+        }
+        if self.errors.len() > rate.max_errors {
+            // Every command is ended even if some fail to, so their subsystems are stopped.
+            if let Err(error) = CommandScheduler::cancel_all() {
+                println!("Error while safing the robot: {}", error);
+            }
+            CommandScheduler::disable();
+            return Err(ErrorRateExceededSnafu { rate }.build().into());
+        }
+        Ok(())
+    }
+}
+
+/// Run the robot's mode callbacks and the [`CommandScheduler`] every [`RobotConfig::period`].
+///
+/// When the robot is disabled, every command that does not run when disabled is cancelled
+/// before [`ScheduledRobot::disabled_init`] is called.
+/// Loop timing is recorded and can be read with [`loop_stats`], and [`MatchTimer`] is
+/// started on entering each mode.
+///
+/// The scheduler is run once per loop unless [`ScheduledRobot::scheduler_run`] returns
+/// [`SchedulerRun::Manual`]. Calling [`CommandScheduler::run`] from the robot's own methods as
+/// well would run every command twice per loop, so a warning is logged if that is detected.
+///
+/// Errors are logged and passed to [`ScheduledRobot::on_error`] without stopping the loop.
+/// If they exceed [`RobotConfig::max_error_rate`], every command is cancelled, the scheduler
+/// is disabled and [`StartRobotError::ErrorRateExceeded`] is returned.
 pub fn start_robot(mut robot: impl ScheduledRobot) -> Result {
+    let config = robot.config();
+    MatchTimer::set_match_type(config.match_type);
+    let mut errors = ErrorHandler::new(&config);
     let mut previous_mode = None;
-    let mut interval = Interval::start();
+    let mut deadline = Instant::now() + config.period;
+    let mut last_start: Option<Instant> = None;
+    let mut warned_extra_runs = false;
 
     loop {
-        let current_mode = competition::mode();
-        match current_mode {
-            CompetitionMode::Disabled => {
-                if previous_mode != Some(CompetitionMode::Disabled) {
-                    robot.disabled_init()?;
-                }
-                robot.disabled_periodic()?;
-            }
-            CompetitionMode::Autonomous => {
-                if previous_mode != Some(CompetitionMode::Autonomous) {
-                    robot.autonomous_init()?;
-                }
-                robot.autonomous_periodic()?;
+        let runs_before = CommandScheduler::run_count();
+        let start = Instant::now();
+        if let Some(last_start) = last_start {
+            LOOP_STATS.with(|stats| {
+                let mut updated = stats.get();
+                updated.record_period(start - last_start);
+                stats.set(updated);
+            });
+        }
+        last_start = Some(start);
+
+        let current_mode = current_mode(&robot);
+        let scheduler_run = robot.scheduler_run();
+        if scheduler_run == SchedulerRun::BeforePeriodic {
+            let result = CommandScheduler::run();
+            errors.handle(&mut robot, current_mode, RobotCallback::Scheduler, result)?;
+        }
+
+        if previous_mode != Some(current_mode) {
+            if let Some(previous_mode) = previous_mode {
+                let result = mode_exit(&mut robot, previous_mode);
+                errors.handle(&mut robot, previous_mode, RobotCallback::ModeExit, result)?;
             }
-            CompetitionMode::Opcontrol => {
-                if previous_mode != Some(CompetitionMode::Opcontrol) {
-                    robot.opcontrol_init()?;
-                }
-                robot.opcontrol_periodic()?;
+            if current_mode == RobotMode::Disabled {
+                let result = CommandScheduler::cancel_disabled_commands();
+                errors.handle(&mut robot, current_mode, RobotCallback::Scheduler, result)?;
             }
+            MatchTimer::start(current_mode);
+            let result = mode_init(&mut robot, current_mode);
+            errors.handle(&mut robot, current_mode, RobotCallback::ModeInit, result)?;
         }
+        let result = mode_periodic(&mut robot, current_mode);
+        errors.handle(&mut robot, current_mode, RobotCallback::ModePeriodic, result)?;
         previous_mode = Some(current_mode);
 
-        robot.periodic()?;
+        let result = robot.periodic();
+        errors.handle(&mut robot, current_mode, RobotCallback::Periodic, result)?;
         if is_sim() {
-            robot.sim_periodic()?;
+            let result = robot.sim_periodic();
+            errors.handle(&mut robot, current_mode, RobotCallback::SimPeriodic, result)?;
+        }
+
+        if scheduler_run == SchedulerRun::AfterPeriodic {
+            let result = CommandScheduler::run();
+            errors.handle(&mut robot, current_mode, RobotCallback::Scheduler, result)?;
+        }
+
+        let runs = CommandScheduler::run_count() - runs_before;
+        if scheduler_run != SchedulerRun::Manual && runs > 1 && !warned_extra_runs {
+            println!(
+                "CommandScheduler::run was called {} times in one loop; start_robot already \
+                 runs it, so remove the extra call or use SchedulerRun::Manual",
+                runs
+            );
+            warned_extra_runs = true;
         }
 
-        interval.delay(ITERATION_PERIOD);
+        deadline = wait_for_next_iteration(&config, deadline);
     }
 }
@@ -0,0 +1,141 @@
+use alloc::vec::Vec;
+
+use crate::filter::SlewRateLimiter;
+
+/// Zeroes values within `deadband` of zero, and rescales the remaining range so the
+/// output still spans -1.0 to 1.0 without a jump at the edge of the deadband.
+pub fn deadband(value: f32, deadband: f32) -> f32 {
+    let magnitude = libm::fabsf(value);
+    if magnitude <= deadband {
+        0.0
+    } else {
+        libm::copysignf((magnitude - deadband) / (1.0 - deadband), value)
+    }
+}
+
+/// Raises the magnitude of the value to the given power, keeping its sign.
+/// Exponents greater than 1.0 give finer control near the center of the joystick.
+pub fn expo(value: f32, exponent: f32) -> f32 {
+    libm::copysignf(libm::powf(libm::fabsf(value), exponent), value)
+}
+
+/// Blends the value with its cube. A `weight` of 0.0 leaves the value linear,
+/// while a `weight` of 1.0 is a pure cubic curve.
+pub fn cubic(value: f32, weight: f32) -> f32 {
+    weight * value * value * value + (1.0 - weight) * value
+}
+
+#[derive(Debug, Clone)]
+enum Stage {
+    Deadband(f32),
+    Expo(f32),
+    Cubic(f32),
+    Scale(f32),
+    SlewRate(SlewRateLimiter),
+}
+
+/// A chain of input shaping stages applied to a single axis, in the order they were added.
+#[derive(Debug, Clone, Default)]
+pub struct AxisShaper {
+    stages: Vec<Stage>,
+}
+
+impl AxisShaper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// See [`deadband`].
+    pub fn deadband(mut self, deadband: f32) -> Self {
+        self.stages.push(Stage::Deadband(deadband));
+        self
+    }
+
+    /// See [`expo`].
+    pub fn expo(mut self, exponent: f32) -> Self {
+        self.stages.push(Stage::Expo(exponent));
+        self
+    }
+
+    /// See [`cubic`].
+    pub fn cubic(mut self, weight: f32) -> Self {
+        self.stages.push(Stage::Cubic(weight));
+        self
+    }
+
+    /// Multiplies the value by a constant, e.g. for a slow mode.
+    pub fn scale(mut self, factor: f32) -> Self {
+        self.stages.push(Stage::Scale(factor));
+        self
+    }
+
+    /// Limits how quickly the value can change, in units per second.
+    pub fn slew_rate(mut self, rate_limit: f32) -> Self {
+        self.stages.push(Stage::SlewRate(SlewRateLimiter::new(rate_limit)));
+        self
+    }
+
+    /// Runs the value through every stage of the shaper.
+    /// The input is clamped to -1.0 to 1.0 first.
+    pub fn shape(&mut self, value: f32) -> f32 {
+        let mut value = value.clamp(-1.0, 1.0);
+        for stage in &mut self.stages {
+            value = match stage {
+                Stage::Deadband(width) => deadband(value, *width),
+                Stage::Expo(exponent) => expo(value, *exponent),
+                Stage::Cubic(weight) => cubic(value, *weight),
+                Stage::Scale(factor) => value * *factor,
+                Stage::SlewRate(limiter) => limiter.calculate(value),
+            };
+        }
+        value
+    }
+}
+
+/// Outputs for the two sides of a differential drivetrain, from -1.0 to 1.0.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct WheelSpeeds {
+    pub left: f32,
+    pub right: f32,
+}
+
+impl WheelSpeeds {
+    /// Drives each side directly from its own input.
+    pub fn tank(left: f32, right: f32) -> Self {
+        Self { left, right }.desaturate()
+    }
+
+    /// Drives from a forward speed and a turn rate. Positive turn rates turn right.
+    pub fn arcade(forward: f32, turn: f32) -> Self {
+        Self {
+            left: forward + turn,
+            right: forward - turn,
+        }
+        .desaturate()
+    }
+
+    /// Drives from a forward speed and a curvature, so that the turning radius stays the
+    /// same regardless of speed. If `turn_in_place` is true, the curvature is used as a
+    /// turn rate instead, which allows turning without driving forward.
+    pub fn curvature(forward: f32, curvature: f32, turn_in_place: bool) -> Self {
+        let turn = if turn_in_place {
+            curvature
+        } else {
+            libm::fabsf(forward) * curvature
+        };
+        Self::arcade(forward, turn)
+    }
+
+    /// Scales both sides down proportionally so that neither exceeds 1.0 in magnitude.
+    fn desaturate(self) -> Self {
+        let max = libm::fabsf(self.left).max(libm::fabsf(self.right));
+        if max > 1.0 {
+            Self {
+                left: self.left / max,
+                right: self.right / max,
+            }
+        } else {
+            self
+        }
+    }
+}
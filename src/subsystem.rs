@@ -1,15 +1,23 @@
-use alloc::{boxed::Box, rc::Rc, vec};
-use core::{cell::RefCell, fmt::Debug};
+use alloc::{boxed::Box, rc::Rc, vec, vec::Vec};
+use core::{cell::RefCell, fmt::Debug, time::Duration};
 use pros::prelude::*;
 
 use crate::{command::{Command, FunctionalCommand}, AnyCommand, CommandScheduler, AnySubsystem, run_once, run, start_end, run_end};
 
 /// A collection of robot parts and other hardware that act together as a whole.
 pub trait Subsystem: Debug {
+    /// A human-readable name for the subsystem, used in logs and debug output.
+    /// Defaults to the name of the implementing type.
+    fn name(&self) -> &str {
+        core::any::type_name::<Self>()
+    }
+
     /// This method will be called once per scheduler run
-    fn periodic(&mut self, ctx: AnySubsystem) {}
+    #[allow(unused_variables)]
+    fn periodic(&mut self, ctx: &SubsystemContext) {}
     /// This method will be called once per scheduler run, but only during simulation
-    fn sim_periodic(&mut self, ctx: AnySubsystem) {}
+    #[allow(unused_variables)]
+    fn sim_periodic(&mut self, ctx: &SubsystemContext) {}
     fn default_command(&self, ctx: AnySubsystem) -> Option<AnyCommand> {
         None
     }
@@ -22,6 +30,54 @@ pub trait Subsystem: Debug {
     }
 }
 
+/// Information about a subsystem's state in the scheduler, passed to [`Subsystem::periodic`].
+#[derive(Debug, Clone)]
+pub struct SubsystemContext {
+    pub(crate) subsystem: AnySubsystem,
+    pub(crate) current_command: Option<AnyCommand>,
+    pub(crate) shared_commands: Vec<AnyCommand>,
+    pub(crate) default_command: Option<AnyCommand>,
+    pub(crate) loop_time: Duration,
+}
+
+impl SubsystemContext {
+    /// A handle to the subsystem itself.
+    pub fn subsystem(&self) -> &AnySubsystem {
+        &self.subsystem
+    }
+
+    /// The command currently requiring exclusive access to the subsystem, if any.
+    pub fn current_command(&self) -> Option<&AnyCommand> {
+        self.current_command.as_ref()
+    }
+
+    /// The commands currently sharing read-only access to the subsystem.
+    pub fn shared_commands(&self) -> &[AnyCommand] {
+        &self.shared_commands
+    }
+
+    /// The default command of the subsystem, if one is set.
+    pub fn default_command(&self) -> Option<&AnyCommand> {
+        self.default_command.as_ref()
+    }
+
+    /// Returns true if no command currently requires exclusive access to the subsystem.
+    pub fn is_idle(&self) -> bool {
+        self.current_command.is_none()
+    }
+
+    /// Returns true if the subsystem's default command is the one currently requiring it.
+    pub fn is_running_default_command(&self) -> bool {
+        self.current_command.is_some() && self.current_command == self.default_command
+    }
+
+    /// The time elapsed since the previous scheduler run.
+    /// This is zero on the first run.
+    pub fn loop_time(&self) -> Duration {
+        self.loop_time
+    }
+}
+
 pub trait SubsystemRefExt {
     fn run_once(&self, action: impl FnMut() -> Result + 'static) -> FunctionalCommand;
     fn run(&self, action: impl FnMut() -> Result + 'static) -> FunctionalCommand;